@@ -0,0 +1,17 @@
+use std::process::Command;
+
+/// Embeds the git commit hash of the build, so that the COBC can identify the running scheduler
+fn main() {
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |hash| hash.trim().to_string());
+
+    println!("cargo:rustc-env=SCHEDULER_BUILD_HASH={hash}");
+}
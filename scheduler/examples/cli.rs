@@ -6,7 +6,11 @@ use std::{
     time::Duration,
 };
 
-use STS1_EDU_Scheduler::communication::{CEPPacket, CommunicationHandle};
+use STS1_EDU_Scheduler::{
+    command::ExternalEvent,
+    communication::{CEPPacket, CommunicationHandle},
+    update,
+};
 
 fn main() {
     let scheduler_path =
@@ -66,15 +70,38 @@ fn write_scheduler_config(path: &str) {
         heartbeat_pin = 34
     update_pin = 35
     heartbeat_freq = 10
+    gpio_backend = \"simulated\"
+    update_pin_file = \"updatepin\"
     log_path = \"log\"
     ",
     )
     .unwrap();
 }
 
-const COMMANDS: &[&str] =
-    &["StoreArchive", "ExecuteProgram", "StopProgram", "GetStatus", "ReturnResult", "UpdateTime"];
+const COMMANDS: &[&str] = &[
+    "StoreArchive",
+    "ExecuteProgram",
+    "StopProgram",
+    "PauseProgram",
+    "ResumeProgram",
+    "GetStatus",
+    "ReturnResult",
+    "UpdateTime",
+    "GetComStatistics",
+    "GetCapabilities",
+    "GetFaultLog",
+    "ReturnLog",
+    "SetLogLevel",
+    "GetConfig",
+    "SetConfig",
+    "StoreUpdate",
+    "ActivateUpdate",
+    "GetTrustedKeys",
+    "GetProgramStore",
+    "ClearProgramStore",
+];
 
+#[allow(clippy::too_many_lines)]
 fn inquire_and_send_command(
     edu: &mut impl CommunicationHandle,
     path: &str,
@@ -89,9 +116,15 @@ fn inquire_and_send_command(
         "StoreArchive" => {
             let archive = inquire::Text::new("Path to zipfile:").prompt()?;
             let program_id = inquire::Text::new("Program id (must be numerical):").prompt()?;
+            let signature =
+                inquire::Text::new("Path to signature (empty if unsigned):").prompt()?;
             let archive = std::fs::read(archive)?;
 
-            edu.send_packet(&CEPPacket::Data(store_archive(program_id.parse()?)))?;
+            let mut command = store_archive(program_id.parse()?);
+            if !signature.is_empty() {
+                command.extend(std::fs::read(signature)?);
+            }
+            edu.send_packet(&CEPPacket::Data(command))?;
             edu.send_multi_packet(&archive)?;
             println!("Received {:?}", edu.receive_packet()?);
         }
@@ -108,6 +141,14 @@ fn inquire_and_send_command(
             edu.send_packet(&CEPPacket::Data(stop_program()))?;
             println!("Received {:?}", edu.receive_packet()?);
         }
+        "PauseProgram" => {
+            edu.send_packet(&CEPPacket::Data(pause_program()))?;
+            println!("Received {:?}", edu.receive_packet()?);
+        }
+        "ResumeProgram" => {
+            edu.send_packet(&CEPPacket::Data(resume_program()))?;
+            println!("Received {:?}", edu.receive_packet()?);
+        }
         "GetStatus" => {
             edu.send_packet(&CEPPacket::Data(get_status()))?;
             if let CEPPacket::Data(status) = edu.receive_packet()? {
@@ -124,9 +165,21 @@ fn inquire_and_send_command(
                         u16::from_le_bytes(status[1..3].try_into()?),
                         u32::from_le_bytes(status[3..7].try_into()?)
                     ),
-                    3 => println!("Enable dosimeter"),
-                    4 => println!("Disable dosimeter"),
-                    n => println!("Unknown event {n}"),
+                    5 => println!(
+                        "Custom event from ID: {} Payload: {:02x?}",
+                        u16::from_le_bytes(status[1..3].try_into()?),
+                        &status[3..]
+                    ),
+                    6 => println!(
+                        "Program with ID: {} Timestamp: {} {}",
+                        u16::from_le_bytes(status[1..3].try_into()?),
+                        u32::from_le_bytes(status[3..7].try_into()?),
+                        if status[7] == 1 { "paused" } else { "resumed" }
+                    ),
+                    n => match ExternalEvent::try_from(status.as_slice()) {
+                        Ok(event) => println!("{event}"),
+                        Err(()) => println!("Unknown event {n}"),
+                    },
                 }
             }
         }
@@ -146,12 +199,161 @@ fn inquire_and_send_command(
                 Err(e) => println!("Received {e:?}"),
             }
         }
+        "GetComStatistics" => {
+            edu.send_packet(&CEPPacket::Data(get_com_statistics()))?;
+            if let CEPPacket::Data(stats) = edu.receive_packet()? {
+                let counters: Vec<u32> = stats
+                    .chunks_exact(4)
+                    .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+                    .collect();
+                println!(
+                    "Session (sent, received, crc, nack, timeout, retransmit): {:?}",
+                    &counters[..6]
+                );
+                println!(
+                    "Lifetime (sent, received, crc, nack, timeout, retransmit): {:?}",
+                    &counters[6..]
+                );
+            }
+        }
+        "GetCapabilities" => {
+            edu.send_packet(&CEPPacket::Data(get_capabilities()))?;
+            if let CEPPacket::Data(caps) = edu.receive_packet()? {
+                let n = usize::from(caps[1]);
+                let rest = &caps[2 + n..];
+                println!("Protocol version: {}", caps[0]);
+                println!("Supported commands: {:x?}", &caps[2..2 + n]);
+                println!("Maximum data length: {}", u16::from_le_bytes([rest[0], rest[1]]));
+                println!("Capabilities: {:#04x}, Transfer modes: {:#04x}", rest[2], rest[3]);
+                println!("Build: {}", String::from_utf8_lossy(&rest[5..]));
+            }
+        }
+        "GetFaultLog" => query_fault_log(edu)?,
+        "ReturnLog" => query_log(edu)?,
+        "SetLogLevel" => change_log_level(edu)?,
+        "SetConfig" => {
+            let assignment =
+                inquire::Text::new("Assignment (e.g. heartbeat_freq = 20):").prompt()?;
+            edu.send_packet(&CEPPacket::Data(set_config(&assignment)))?;
+            match edu.receive_packet()? {
+                CEPPacket::Data(d) if d == [0] => println!("Applied"),
+                CEPPacket::Data(d) if d == [1] => println!("Applied after restart"),
+                p => println!("Received {p:?}"),
+            }
+        }
+        "GetConfig" => {
+            let key = inquire::Text::new("Key (empty for all):").prompt()?;
+            edu.send_packet(&CEPPacket::Data(get_config(&key)))?;
+            if let CEPPacket::Data(config) = edu.receive_packet()? {
+                println!("{}", String::from_utf8_lossy(&config));
+            }
+        }
+        "StoreUpdate" => upload_update(edu)?,
+        "ActivateUpdate" => {
+            edu.send_packet(&CEPPacket::Data(activate_update()))?;
+            println!("Received {:?}", edu.receive_packet()?);
+        }
+        "GetTrustedKeys" => {
+            edu.send_packet(&CEPPacket::Data(get_trusted_keys()))?;
+            if let CEPPacket::Data(keys) = edu.receive_packet()? {
+                println!("{} trusted keys", keys[0]);
+                for key in keys[1..].chunks_exact(32) {
+                    println!(
+                        "{}",
+                        key.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().concat()
+                    );
+                }
+            }
+        }
+        "GetProgramStore" => query_program_store(edu)?,
+        "ClearProgramStore" => {
+            let program_id = inquire::Text::new("Program id:").prompt()?.parse()?;
+            edu.send_packet(&CEPPacket::Data(clear_program_store(program_id)))?;
+            println!("Received {:?}", edu.receive_packet()?);
+        }
         _ => (),
     }
 
     Ok(())
 }
 
+fn query_program_store(edu: &mut impl CommunicationHandle) -> Result<(), Box<dyn Error>> {
+    let program_id = inquire::Text::new("Program id:").prompt()?.parse()?;
+    edu.send_packet(&CEPPacket::Data(get_program_store(program_id)))?;
+    let mut store = &edu.receive_multi_packet()?[..];
+    edu.send_packet(&CEPPacket::Ack)?;
+
+    while let Some((&key_length, rest)) = store.split_first() {
+        let (key, rest) = rest.split_at(usize::from(key_length));
+        let value_length = u32::from_le_bytes(rest[..4].try_into()?) as usize;
+        let (value, rest) = rest[4..].split_at(value_length);
+        println!("{}: {}", String::from_utf8_lossy(key), String::from_utf8_lossy(value));
+        store = rest;
+    }
+
+    Ok(())
+}
+
+fn upload_update(edu: &mut impl CommunicationHandle) -> Result<(), Box<dyn Error>> {
+    let path = inquire::Text::new("Path to scheduler binary:").prompt()?;
+    let signature = inquire::Text::new("Path to signature (empty if unsigned):").prompt()?;
+    let binary = std::fs::read(path)?;
+
+    let mut command = store_update(&update::sha256(&binary));
+    if !signature.is_empty() {
+        command.extend(std::fs::read(signature)?);
+    }
+    edu.send_packet(&CEPPacket::Data(command))?;
+    edu.send_multi_packet(&binary)?;
+    println!("Received {:?}", edu.receive_packet()?);
+
+    Ok(())
+}
+
+fn query_fault_log(edu: &mut impl CommunicationHandle) -> Result<(), Box<dyn Error>> {
+    let clear = inquire::Confirm::new("Clear the log afterwards?").prompt()?;
+    edu.send_packet(&CEPPacket::Data(get_fault_log(clear)))?;
+    if let CEPPacket::Data(log) = edu.receive_packet()? {
+        println!(
+            "{} records (timestamp, command, class, fault, detail):",
+            u16::from_le_bytes([log[0], log[1]])
+        );
+        for r in log[2..].chunks_exact(9) {
+            let timestamp = u32::from_le_bytes([r[0], r[1], r[2], r[3]]);
+            let detail = u16::from_le_bytes([r[7], r[8]]);
+            println!("{timestamp} {:#04x} {} {} {detail}", r[4], r[5], r[6]);
+        }
+    }
+
+    Ok(())
+}
+
+fn query_log(edu: &mut impl CommunicationHandle) -> Result<(), Box<dyn Error>> {
+    let index = inquire::Text::new("Log index (0 is the current log):").prompt()?.parse()?;
+    let log_path =
+        inquire::Text::new("File path for returned log:").with_default("./log").prompt()?;
+    edu.send_packet(&CEPPacket::Data(return_log(index)))?;
+    match edu.receive_multi_packet() {
+        Ok(data) => {
+            std::fs::write(log_path, data)?;
+            println!("Wrote log to file");
+        }
+        Err(e) => println!("Received {e:?}"),
+    }
+
+    Ok(())
+}
+
+fn change_log_level(edu: &mut impl CommunicationHandle) -> Result<(), Box<dyn Error>> {
+    let levels = vec!["off", "error", "warn", "info", "debug", "trace"];
+    let level = inquire::Select::new("Log level:", levels).raw_prompt()?.index;
+    let module = inquire::Text::new("Module (empty for all):").prompt()?;
+    edu.send_packet(&CEPPacket::Data(set_log_level(level.try_into()?, &module)))?;
+    println!("Received {:?}", edu.receive_packet()?);
+
+    Ok(())
+}
+
 impl<T: Read, U: Write> Read for SocatSerialPort<T, U> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.stdout.read(buf)
@@ -221,3 +423,85 @@ pub fn return_result(program_id: u16, timestamp: u32) -> Vec<u8> {
     vec.extend(timestamp.to_le_bytes());
     vec
 }
+
+#[must_use]
+pub fn get_com_statistics() -> Vec<u8> {
+    vec![7u8]
+}
+
+#[must_use]
+pub fn get_capabilities() -> Vec<u8> {
+    vec![9u8]
+}
+
+#[must_use]
+pub fn get_fault_log(clear: bool) -> Vec<u8> {
+    vec![10u8, u8::from(clear)]
+}
+
+#[must_use]
+pub fn return_log(index: u8) -> Vec<u8> {
+    vec![11u8, index]
+}
+
+#[must_use]
+pub fn get_config(key: &str) -> Vec<u8> {
+    let mut vec = vec![13u8];
+    vec.extend(key.as_bytes());
+    vec
+}
+
+#[must_use]
+pub fn set_config(assignment: &str) -> Vec<u8> {
+    let mut vec = vec![14u8];
+    vec.extend(assignment.as_bytes());
+    vec
+}
+
+#[must_use]
+pub fn set_log_level(level: u8, module: &str) -> Vec<u8> {
+    let mut vec = vec![12u8, level];
+    vec.extend(module.as_bytes());
+    vec
+}
+
+#[must_use]
+pub fn store_update(checksum: &[u8]) -> Vec<u8> {
+    let mut vec = vec![15u8];
+    vec.extend(checksum);
+    vec
+}
+
+#[must_use]
+pub fn activate_update() -> Vec<u8> {
+    vec![16u8]
+}
+
+#[must_use]
+pub fn get_trusted_keys() -> Vec<u8> {
+    vec![17u8]
+}
+
+#[must_use]
+pub fn get_program_store(program_id: u16) -> Vec<u8> {
+    let mut vec = vec![18u8];
+    vec.extend(program_id.to_le_bytes());
+    vec
+}
+
+#[must_use]
+pub fn clear_program_store(program_id: u16) -> Vec<u8> {
+    let mut vec = vec![19u8];
+    vec.extend(program_id.to_le_bytes());
+    vec
+}
+
+#[must_use]
+pub fn pause_program() -> Vec<u8> {
+    vec![20u8]
+}
+
+#[must_use]
+pub fn resume_program() -> Vec<u8> {
+    vec![21u8]
+}
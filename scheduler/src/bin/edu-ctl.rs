@@ -0,0 +1,154 @@
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{fs::MetadataExt, net::UnixStream},
+    process::ExitCode,
+    time::Duration,
+};
+use STS1_EDU_Scheduler::config::Configuration;
+
+/// How often `edu-ctl log -f` checks for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+const USAGE: &str = "\
+Usage: edu-ctl [--config <path>] <command>
+
+Talks to the scheduler through the socket given in the configuration (default ./config.toml).
+
+Commands:
+    status                                  Show the build, running program and queued events
+    events                                  List the events waiting to be sent to the COBC
+    programs                                List the stored programs
+    statistics                              Show the communication statistics
+    event <name> [<field>=<value>...]       Queue an event, e.g. dosimeter/on
+    execute <program> <timestamp> <timeout> Start a program, the timeout is given in seconds
+    stop                                    Stop the running program
+    log-filter <directives>                 Change the log filter, e.g. warn,communication=debug
+    log [-f] [lines]                        Print the last lines of the log, -f keeps following it
+";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config_path = "./config.toml".to_string();
+    if args.first().is_some_and(|a| a == "--config") && args.len() >= 2 {
+        config_path = args.remove(1);
+        args.remove(0);
+    }
+
+    // like the scheduler, fall back to the defaults of invalid keys, so that both use the same socket
+    let (config, errors) = Configuration::load_lenient(&config_path);
+    for e in errors {
+        eprintln!("edu-ctl: {config_path}: {e}");
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["log", args @ ..] => tail_log(&config, args),
+        args => {
+            let Some(request) = build_request(args) else {
+                eprint!("{USAGE}");
+                return ExitCode::from(2);
+            };
+            send_request(&config, &request)
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("edu-ctl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Translates the command line into a request for the control socket
+fn build_request(args: &[&str]) -> Option<Value> {
+    let request = match args {
+        ["status"] => json!({ "request": "status" }),
+        ["events"] => json!({ "request": "events" }),
+        ["programs"] => json!({ "request": "programs" }),
+        ["statistics"] => json!({ "request": "statistics" }),
+        ["event", name, fields @ ..] => {
+            let mut payload = serde_json::Map::new();
+            for field in fields {
+                let (key, value) = field.split_once('=')?;
+                payload.insert(key.to_string(), value.parse::<u64>().ok()?.into());
+            }
+            json!({ "request": "event", "event": name, "payload": payload })
+        }
+        ["execute", program_id, timestamp, timeout] => json!({
+            "request": "execute",
+            "program_id": program_id.parse::<u16>().ok()?,
+            "timestamp": timestamp.parse::<u32>().ok()?,
+            "timeout": timeout.parse::<u16>().ok()?,
+        }),
+        ["stop"] => json!({ "request": "stop" }),
+        ["log-filter", directives] => json!({ "request": "log_filter", "directives": directives }),
+        _ => return None,
+    };
+    Some(request)
+}
+
+/// Sends a single request and prints the answer
+fn send_request(config: &Configuration, request: &Value) -> Result<(), Box<dyn Error>> {
+    let mut stream = UnixStream::connect(&config.socket)
+        .map_err(|e| format!("could not connect to {}: {e}", config.socket))?;
+    writeln!(stream, "{request}")?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let response: Value = serde_json::from_str(&line)?;
+    if let Some(error) = response.get("error") {
+        return Err(error.as_str().unwrap_or("unknown error").into());
+    }
+
+    match &response["ok"] {
+        Value::Null => (),
+        Value::String(s) => println!("{s}"),
+        value => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+/// Prints the last lines of the log. When following, lines are printed as they are appended, and
+/// the new log is opened once the current one was rotated.
+fn tail_log(config: &Configuration, args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let (follow, args) = match args {
+        ["-f", args @ ..] => (true, args),
+        args => (false, args),
+    };
+    let lines = match args {
+        [] => 10,
+        [lines] => lines.parse()?,
+        _ => return Err(USAGE.into()),
+    };
+
+    let mut file = File::open(&config.log_path)?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let content = String::from_utf8_lossy(&content);
+    let skip = content.lines().count().saturating_sub(lines);
+    for line in content.lines().skip(skip) {
+        println!("{line}");
+    }
+
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        std::thread::sleep(FOLLOW_INTERVAL);
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        print!("{}", String::from_utf8_lossy(&appended));
+        std::io::stdout().flush()?;
+
+        let rotated = std::fs::metadata(&config.log_path)
+            .is_ok_and(|current| current.ino() != file.metadata().map_or(0, |m| m.ino()));
+        if rotated {
+            file = File::open(&config.log_path)?;
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use super::{
+    check_length, send_nack, terminate_student_program, CommandError, CommandResult, NackReason,
+    SyncExecutionContext,
+};
+use crate::{
+    communication::{CEPPacket, CommunicationHandle},
+    update::{self, UpdateError, Updater},
+};
+use anyhow::anyhow;
+
+/// Installs the staged scheduler update and restarts into it. A running student program is
+/// stopped first. The new binary is on trial until all monitored threads stayed responsive for
+/// `update_health_window_s`, see [`update::supervise_trial`].
+pub fn activate_update(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    let config = exec.lock().unwrap().config.clone();
+    let updater = Updater::for_current_executable(&config)?;
+    if !updater.is_staged() {
+        return send_nack(
+            com,
+            NackReason::UpdateNotStaged,
+            CommandError::ProtocolViolation(anyhow!("No update is staged")),
+        );
+    }
+
+    if let Err((reason, e)) = terminate_student_program(exec) {
+        return send_nack(com, reason, e);
+    }
+    match updater.install() {
+        Ok(()) => (),
+        Err(UpdateError::Io(e)) => return send_nack(com, NackReason::Storage, e.into()),
+        Err(e) => return Err(CommandError::NonRecoverable(e.into())),
+    }
+    com.send_packet(&CEPPacket::Ack)?;
+
+    if let Some(statistics) = com.statistics() {
+        if let Err(e) = statistics.lock().unwrap().save() {
+            log::error!("Could not persist communication statistics: {e}");
+        }
+    }
+    log::info!("Restarting into update");
+    let e = update::restart(updater.executable());
+    // the old binary keeps running, so it must not be replaced on the next start
+    if let Err(e) = updater.rollback() {
+        log::error!("Could not roll back update: {e}");
+    }
+    Err(e.into())
+}
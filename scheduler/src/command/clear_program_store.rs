@@ -0,0 +1,23 @@
+use super::{
+    check_length, send_nack, CommandResult, NackReason, ProgramStore, SyncExecutionContext,
+};
+use crate::communication::{CEPPacket, CommunicationHandle};
+
+/// Removes all entries from the store of the program with the given id
+pub fn clear_program_store(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 3)?;
+
+    let program_id = u16::from_le_bytes([data[1], data[2]]);
+    let store = ProgramStore::new(&exec.lock().unwrap().config, program_id);
+    if let Err(e) = store.clear() {
+        return send_nack(com, NackReason::Storage, e.into());
+    }
+
+    log::info!("Cleared store of program {program_id}");
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
@@ -1,8 +1,15 @@
-use super::{CommandError, CommandResult, SyncExecutionContext};
-use crate::communication::{CEPPacket, CommunicationHandle};
-use anyhow::anyhow;
+use super::{
+    CommandError, CommandResult, Event, NackReason, ProgramState, RetryEvent, SupervisorError,
+    SyncExecutionContext,
+};
+use crate::communication::{CEPPacket, Capabilities, CommunicationHandle};
+use anyhow::{anyhow, bail};
+use ed25519_dalek::{Signature, VerifyingKey};
 use std::time::Duration;
 
+/// How often `terminate_student_program` checks wether the supervisor thread finished
+const TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub fn check_length(
     com: &mut impl CommunicationHandle,
     vec: &[u8],
@@ -12,36 +19,234 @@ pub fn check_length(
     if actual_len == n {
         Ok(())
     } else {
-        com.send_packet(&CEPPacket::Nack)?;
-        Err(CommandError::ProtocolViolation(anyhow!(
-            "Received command with {actual_len} bytes, expected {n}"
-        )))
+        send_nack(
+            com,
+            NackReason::InvalidLength,
+            CommandError::ProtocolViolation(anyhow!(
+                "Received command with {actual_len} bytes, expected {n}"
+            )),
+        )
     }
 }
 
+/// Like `check_length`, but accepts any length in the range `min..=max`. Used by commands with
+/// optional trailing parameters.
+pub fn check_length_between(
+    com: &mut impl CommunicationHandle,
+    vec: &[u8],
+    min: usize,
+    max: usize,
+) -> Result<(), CommandError> {
+    let actual_len = vec.len();
+    if (min..=max).contains(&actual_len) {
+        Ok(())
+    } else {
+        send_nack(
+            com,
+            NackReason::InvalidLength,
+            CommandError::ProtocolViolation(anyhow!(
+                "Received command with {actual_len} bytes, expected {min} to {max}"
+            )),
+        )
+    }
+}
+
+/// Answers the COBC with a NACK and returns the given error. If the COBC negotiated
+/// `Capabilities::NACK_REASON`, the NACK is followed by a data packet describing the failure.
+pub fn send_nack(
+    com: &mut impl CommunicationHandle,
+    reason: NackReason,
+    error: CommandError,
+) -> CommandResult {
+    com.send_packet(&CEPPacket::Nack)?;
+    if com.capabilities().contains(Capabilities::NACK_REASON) {
+        com.send_packet(&CEPPacket::Data(error.nack_payload(reason)))?;
+    }
+    Err(error)
+}
+
+/// Checks that `message` was signed by one of the `keys`, see `Configuration::trusted_keys`
+pub(super) fn verify_signature(
+    keys: &[VerifyingKey],
+    message: &[u8],
+    signature: &[u8],
+) -> anyhow::Result<()> {
+    if signature.is_empty() {
+        bail!("Not signed");
+    }
+
+    let signature = Signature::from_slice(signature)?;
+    if !keys.iter().any(|key| key.verify_strict(message, &signature).is_ok()) {
+        bail!("Not signed by a trusted key");
+    }
+    Ok(())
+}
+
 /// If no program is currently running, this function simply returns. Otherwise it signals the
-/// supervisor thread to kill the student program and waits for a maximum of 2s before returning
-/// and error
-pub fn terminate_student_program(exec: &mut SyncExecutionContext) -> CommandResult {
+/// supervisor thread to kill the student program and waits up to the configured
+/// `terminate_timeout_ms` before returning an error. On failure, the reason to report to the COBC
+/// is returned along with the error.
+pub fn terminate_student_program(
+    exec: &mut SyncExecutionContext,
+) -> Result<(), (NackReason, CommandError)> {
+    let lifecycle = exec.lock().unwrap().lifecycle.clone();
+    let _lifecycle = lifecycle.lock().unwrap();
+    terminate_locked(exec)
+}
+
+/// Like `terminate_student_program`, for callers that already hold `ExecutionContext::lifecycle`
+pub(super) fn terminate_locked(
+    exec: &mut SyncExecutionContext,
+) -> Result<(), (NackReason, CommandError)> {
     let mut con = exec.lock().unwrap();
     if !con.is_student_program_running() {
         return Ok(());
     }
     con.running_flag = false; // Signal watchdog thread to terminate
+    let polls = con.config.terminate_timeout().as_millis() / TERMINATE_POLL_INTERVAL.as_millis();
     drop(con); // Release mutex
 
-    for _ in 0..20 {
-        std::thread::sleep(Duration::from_millis(100)); // Sensible amount?
+    for _ in 0..polls.max(1) {
+        std::thread::sleep(TERMINATE_POLL_INTERVAL);
         let mut con = exec.lock().unwrap();
-        if con.thread_handle.as_ref().unwrap().is_finished() {
-            con.thread_handle
-                .take()
-                .unwrap()
-                .join()
-                .or(Err(CommandError::NonRecoverable(anyhow!("Supervisor thread panicked"))))?;
+        // the supervisor may have been abandoned by the recovery in the meantime
+        let Some(handle) = con.thread_handle.take() else {
+            return Err((
+                NackReason::ProgramNotRunning,
+                CommandError::ProtocolViolation(anyhow!("The supervisor was abandoned")),
+            ));
+        };
+        if handle.is_finished() {
+            drop(con);
+            return handle.join().map_err(|_| {
+                (
+                    NackReason::ProgramNotStopped,
+                    CommandError::NonRecoverable(SupervisorError::Panicked.into()),
+                )
+            });
+        }
+        con.thread_handle = Some(handle);
+    }
+
+    Err((
+        NackReason::ProgramNotStopped,
+        CommandError::NonRecoverable(SupervisorError::Timeout.into()),
+    ))
+}
+
+/// Stops (SIGSTOP) or continues (SIGCONT) the process group of the running student program. The
+/// change is reported to the COBC with a state event, nothing happens if the program already is in
+/// the requested state. If the event can not be stored, the signal is reverted. On failure, the
+/// reason to report to the COBC is returned along with the error.
+pub fn pause_student_program(
+    exec: &mut SyncExecutionContext,
+    pause: bool,
+) -> Result<(), (NackReason, CommandError)> {
+    let mut con = exec.lock().unwrap();
+    let (Some(pid), Some(session)) = (con.student_pid, &con.api_session) else {
+        return Err((
+            NackReason::ProgramNotRunning,
+            CommandError::ProtocolViolation(anyhow!("No student program is running")),
+        ));
+    };
+    if con.paused == pause {
+        return Ok(());
+    }
+    let (program_id, timestamp) = (session.program_id, session.timestamp);
+
+    let (signal, revert) =
+        if pause { (libc::SIGSTOP, libc::SIGCONT) } else { (libc::SIGCONT, libc::SIGSTOP) };
+    match signal_group(pid, signal) {
+        Ok(()) => (),
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {
+            // the program exited, its supervisor has not cleaned up yet
+            return Err((
+                NackReason::ProgramNotRunning,
+                CommandError::ProtocolViolation(anyhow!("Process group {pid} is gone")),
+            ));
+        }
+        Err(e) => {
+            let reason =
+                if pause { NackReason::ProgramNotStopped } else { NackReason::ProgramNotStarted };
+            return Err((reason, e.into()));
+        }
+    }
+
+    let state = ProgramState { program_id, timestamp, paused: pause };
+    let tries = con.config.event_send_tries;
+    if let Err(e) = con.events.push(RetryEvent::new(Event::State(state), tries)) {
+        if let Err(e) = signal_group(pid, revert) {
+            log::error!("Could not revert signal {signal} to process group {pid}: {e}");
+        }
+        return Err((NackReason::Storage, e.into()));
+    }
+
+    con.paused = pause;
+    log::info!("Program {program_id}:{timestamp} {}", if pause { "paused" } else { "resumed" });
+    Ok(())
+}
+
+/// Kills every process in the group of a student program and waits up to `timeout` for them to
+/// exit. A paused group is continued as well, so that no stopped process is left behind.
+pub fn kill_student_group(pid: u32, paused: bool, timeout: Duration) -> std::io::Result<()> {
+    let killed = signal_group(pid, libc::SIGKILL).and_then(|()| {
+        if paused {
+            signal_group(pid, libc::SIGCONT)
+        } else {
+            Ok(())
+        }
+    });
+    match killed {
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => return Ok(()), // the group is gone
+        result => result?,
+    }
+
+    let polls = timeout.as_millis() / TERMINATE_POLL_INTERVAL.as_millis();
+    for _ in 0..polls.max(1) {
+        if !group_is_alive(pid) {
             return Ok(());
         }
+        std::thread::sleep(TERMINATE_POLL_INTERVAL);
+    }
+
+    Err(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        format!("Process group {pid} is still alive after SIGKILL"),
+    ))
+}
+
+/// Sends `signal` to the process group led by `pid`
+fn signal_group(pid: u32, signal: libc::c_int) -> std::io::Result<()> {
+    let group = libc::pid_t::try_from(pid).map_err(std::io::Error::other)?;
+    // SAFETY: killpg only sends a signal and has no memory safety requirements
+    if unsafe { libc::killpg(group, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
     }
+}
+
+/// Returns wether a process of the group led by `pid` is still alive. Zombies are not counted, they
+/// only wait for their parent to reap them.
+#[must_use]
+pub fn group_is_alive(pid: u32) -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    let group = pid.to_string();
 
-    Err(CommandError::NonRecoverable(anyhow!("Supervisor thread did not finish in time")))
+    entries.filter_map(Result::ok).any(|entry| {
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            return false;
+        };
+        // The fields following the executable name, which may contain spaces, start with the
+        // state, the parent and the process group
+        let Some((_, fields)) = stat.rsplit_once(')') else {
+            return false;
+        };
+        let mut fields = fields.split_whitespace();
+        let state = fields.next();
+        let group_of_process = fields.nth(1);
+        state != Some("Z") && group_of_process == Some(group.as_str())
+    })
 }
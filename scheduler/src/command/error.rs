@@ -10,6 +10,77 @@ pub enum CommandError {
     ProtocolViolation(anyhow::Error),
 }
 
+impl CommandError {
+    /// Identifies the variant on the wire
+    #[must_use]
+    pub fn class(&self) -> u8 {
+        match self {
+            CommandError::ProtocolViolation(_) => 0x01,
+            CommandError::External(_) => 0x02,
+            CommandError::NonRecoverable(_) => 0x03,
+        }
+    }
+
+    /// The OS error code if this error was caused by an IO error, 0 otherwise
+    #[must_use]
+    pub fn detail(&self) -> u16 {
+        let (CommandError::NonRecoverable(e)
+        | CommandError::External(e)
+        | CommandError::ProtocolViolation(e)) = self;
+
+        e.chain()
+            .find_map(|e| e.downcast_ref::<std::io::Error>())
+            .and_then(std::io::Error::raw_os_error)
+            .and_then(|code| u16::try_from(code).ok())
+            .unwrap_or(0)
+    }
+
+    /// Builds the payload of the data packet that follows a NACK if the COBC negotiated
+    /// `Capabilities::NACK_REASON`. It consists of the error class, the reason and the detail code.
+    #[must_use]
+    pub fn nack_payload(&self, reason: NackReason) -> Vec<u8> {
+        let mut v = vec![self.class(), reason as u8];
+        v.extend(self.detail().to_le_bytes());
+        v
+    }
+}
+
+/// The specific reason why a command was answered with a NACK
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum NackReason {
+    /// The command had an unexpected length
+    InvalidLength = 0x01,
+    /// The command id is not known to this scheduler
+    UnknownCommand = 0x02,
+    /// No program with the requested id is stored
+    ProgramNotFound = 0x03,
+    /// The student program could not be started
+    ProgramNotStarted = 0x04,
+    /// No result with the requested id exists
+    ResultNotFound = 0x05,
+    /// The received archive could not be unpacked
+    InvalidArchive = 0x06,
+    /// Reading or writing a file failed
+    Storage = 0x07,
+    /// The requested feature is not supported by this scheduler
+    Unsupported = 0x08,
+    /// The running student program could not be stopped
+    ProgramNotStopped = 0x09,
+    /// No log with the requested index exists
+    LogNotFound = 0x0A,
+    /// A parameter of the command is out of range or malformed
+    InvalidParameter = 0x0B,
+    /// The received data does not match its checksum
+    InvalidChecksum = 0x0C,
+    /// An update was to be activated, but none is staged
+    UpdateNotStaged = 0x0D,
+    /// Signatures are required, but the archive or update is not signed by a trusted key
+    InvalidSignature = 0x0E,
+    /// The command requires a running student program, but none is running
+    ProgramNotRunning = 0x0F,
+}
+
 impl From<std::io::Error> for CommandError {
     fn from(e: std::io::Error) -> Self {
         CommandError::NonRecoverable(e.into())
@@ -0,0 +1,67 @@
+use crate::gpio::OutputPin;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Signals the COBC wether events are waiting to be fetched with Get Status. It is driven by the
+/// [`super::EventQueue`], so the signal always matches the queue's content.
+pub trait EventNotifier: Send {
+    fn set_pending(&mut self, pending: bool);
+}
+
+/// Drives the `EDU_Update` pin
+pub struct GpioNotifier {
+    pin: Box<dyn OutputPin>,
+}
+
+impl GpioNotifier {
+    #[must_use]
+    pub fn new(pin: Box<dyn OutputPin>) -> Self {
+        Self { pin }
+    }
+}
+
+impl EventNotifier for GpioNotifier {
+    fn set_pending(&mut self, pending: bool) {
+        self.pin.set(pending);
+    }
+}
+
+/// Creates the given file while events are pending. Used to observe the scheduler from another
+/// process when simulating.
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl EventNotifier for FileNotifier {
+    fn set_pending(&mut self, pending: bool) {
+        if pending {
+            let _ = std::fs::write(&self.path, b"");
+        } else {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Only keeps the state in memory. Clones share the same state, so it can be observed while the
+/// notifier is owned by the queue.
+#[derive(Clone, Default)]
+pub struct MemoryNotifier {
+    pub pending: Arc<AtomicBool>,
+}
+
+impl EventNotifier for MemoryNotifier {
+    fn set_pending(&mut self, pending: bool) {
+        self.pending.store(pending, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,149 @@
+use super::{Event, EventNotifier, RetryEvent};
+use filevec::FileVec;
+use std::path::Path;
+
+/// The persistent queue of events that should be sent to the COBC. Every change is reported to an
+/// [`EventNotifier`], so the COBC is signalled exactly while the queue is not empty.
+pub struct EventQueue {
+    events: FileVec<RetryEvent<Event>>,
+    notifier: Box<dyn EventNotifier>,
+}
+
+impl EventQueue {
+    /// Opens the queue stored in the given file and signals its current state
+    pub fn open(path: impl AsRef<Path>, notifier: Box<dyn EventNotifier>) -> std::io::Result<Self> {
+        let mut queue = Self { events: FileVec::open(path)?, notifier };
+        queue.notify();
+        Ok(queue)
+    }
+
+    pub fn push(&mut self, event: RetryEvent<Event>) -> std::io::Result<()> {
+        let result = self.events.push(event);
+        self.notify();
+        result
+    }
+
+    /// Queues the event, replacing the last queued event if it is superseded by the new one.
+    /// Returns wether an event was replaced.
+    pub fn push_coalesced(&mut self, event: RetryEvent<Event>) -> std::io::Result<bool> {
+        let replaced =
+            self.events.as_ref().last().is_some_and(|last| event.event.supersedes(&last.event));
+        if replaced {
+            // the push below writes the whole queue, so it reports any error of the removal as well
+            let _ = self.events.pop();
+        }
+        let result = self.events.push(event);
+        self.notify();
+        result.map(|()| replaced)
+    }
+
+    pub fn remove(&mut self, index: usize) -> std::io::Result<RetryEvent<Event>> {
+        let result = self.events.remove(index);
+        self.notify();
+        result
+    }
+
+    /// Gives mutable access to the events. They are persisted and signalled once `f` returns.
+    pub fn modify<R>(&mut self, f: impl FnOnce(&mut Vec<RetryEvent<Event>>) -> R) -> R {
+        let result = f(&mut self.events.as_mut());
+        self.notify();
+        result
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.as_ref().is_empty()
+    }
+
+    fn notify(&mut self) {
+        let pending = !self.is_empty();
+        self.notifier.set_pending(pending);
+    }
+}
+
+impl AsRef<Vec<RetryEvent<Event>>> for EventQueue {
+    fn as_ref(&self) -> &Vec<RetryEvent<Event>> {
+        self.events.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{EventType, MemoryNotifier, ProgramStatus};
+    use std::sync::atomic::Ordering;
+
+    fn external(name: &str) -> Event {
+        EventType::by_name(name).unwrap().event(&serde_json::Map::new()).unwrap()
+    }
+
+    #[test]
+    fn notifier_follows_queue() {
+        let path = "__event_queue_notifier";
+        let _ = std::fs::remove_file(path);
+        let notifier = MemoryNotifier::default();
+
+        let mut queue = EventQueue::open(path, Box::new(notifier.clone())).unwrap();
+        assert!(!notifier.pending.load(Ordering::Relaxed));
+        queue.push(RetryEvent::new(external("dosimeter/on"), 1)).unwrap();
+        assert!(notifier.pending.load(Ordering::Relaxed));
+        queue.modify(Vec::clear);
+        assert!(!notifier.pending.load(Ordering::Relaxed));
+        queue.push(RetryEvent::new(external("dosimeter/off"), 1)).unwrap();
+        drop(queue);
+
+        let other = MemoryNotifier::default();
+        let mut queue = EventQueue::open(path, Box::new(other.clone())).unwrap();
+        assert!(other.pending.load(Ordering::Relaxed));
+        queue.remove(0).unwrap();
+        assert!(!other.pending.load(Ordering::Relaxed));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn queue_of_earlier_versions_is_read() {
+        let path = "__event_queue_earlier_version";
+        // [[5, "EnableDosimeter"], [3, "DisableDosimeter"], [1, {"Status": [1, 2, 0]}]]
+        let mut stored = vec![0x93, 0x92, 0x05, 0xaf];
+        stored.extend(b"EnableDosimeter");
+        stored.extend([0x92, 0x03, 0xb0]);
+        stored.extend(b"DisableDosimeter");
+        stored.extend([0x92, 0x01, 0x81, 0xa6]);
+        stored.extend(b"Status");
+        stored.extend([0x93, 0x01, 0x02, 0x00]);
+        std::fs::write(path, stored).unwrap();
+
+        let queue = EventQueue::open(path, Box::new(MemoryNotifier::default())).unwrap();
+        let status = Event::Status(ProgramStatus { program_id: 1, timestamp: 2, exit_code: 0 });
+        assert_eq!(
+            queue.as_ref(),
+            &[
+                RetryEvent::new(external("dosimeter/on"), 5),
+                RetryEvent::new(external("dosimeter/off"), 3),
+                RetryEvent::new(status, 1),
+            ]
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn toggles_are_coalesced() {
+        let path = "__event_queue_coalesced";
+        let _ = std::fs::remove_file(path);
+        let mut queue = EventQueue::open(path, Box::new(MemoryNotifier::default())).unwrap();
+
+        assert!(!queue.push_coalesced(RetryEvent::new(external("dosimeter/on"), 1)).unwrap());
+        assert!(queue.push_coalesced(RetryEvent::new(external("dosimeter/off"), 1)).unwrap());
+        assert!(queue.push_coalesced(RetryEvent::new(external("dosimeter/on"), 1)).unwrap());
+        assert_eq!(queue.as_ref(), &[RetryEvent::new(external("dosimeter/on"), 1)]);
+
+        let status = Event::Status(ProgramStatus { program_id: 1, timestamp: 2, exit_code: 0 });
+        assert!(!queue.push_coalesced(RetryEvent::new(status, 1)).unwrap());
+        assert!(!queue.push_coalesced(RetryEvent::new(external("dosimeter/off"), 1)).unwrap());
+        assert_eq!(queue.as_ref().len(), 3);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
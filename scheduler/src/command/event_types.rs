@@ -0,0 +1,237 @@
+use super::Event;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+
+/// The maximum number of bytes in the payload of an external event
+pub const MAXIMUM_EXTERNAL_PAYLOAD: usize = 16;
+
+/// The events other processes on the EDU can queue for the COBC through the control socket. A new
+/// trigger only needs an entry here, the socket, the wire encoding and the decoding follow from it.
+///
+/// The ids 1, 2, 5 and 6 are taken by the status, result, custom and state events of the scheduler
+/// itself.
+pub const EVENT_TYPES: &[EventType] = &[
+    EventType { name: "dosimeter/on", id: 3, fields: &[], group: Some("dosimeter") },
+    EventType { name: "dosimeter/off", id: 4, fields: &[], group: Some("dosimeter") },
+];
+
+/// The type of a payload field, which is sent little endian
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+}
+
+impl FieldType {
+    fn size(self) -> usize {
+        match self {
+            FieldType::U8 => 1,
+            FieldType::U16 => 2,
+            FieldType::U32 => 4,
+        }
+    }
+
+    fn maximum(self) -> u64 {
+        match self {
+            FieldType::U8 => u8::MAX.into(),
+            FieldType::U16 => u16::MAX.into(),
+            FieldType::U32 => u32::MAX.into(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct EventType {
+    /// The name the event is queued with on the control socket
+    pub name: &'static str,
+    /// The first byte of the event, when it is sent to the COBC
+    pub id: u8,
+    /// The payload, which follows the id in this order
+    pub fields: &'static [(&'static str, FieldType)],
+    /// A queued event is replaced by a newer one of the same group, see `Event::supersedes`
+    pub group: Option<&'static str>,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PayloadError {
+    #[error("missing field {0:?}")]
+    MissingField(&'static str),
+    #[error("unknown field {0:?}")]
+    UnknownField(String),
+    #[error("{field} must be an integer between 0 and {maximum}")]
+    InvalidValue { field: &'static str, maximum: u64 },
+}
+
+impl EventType {
+    #[must_use]
+    pub fn by_name(name: &str) -> Option<&'static EventType> {
+        EVENT_TYPES.iter().find(|t| t.name == name)
+    }
+
+    #[must_use]
+    pub fn by_id(id: u8) -> Option<&'static EventType> {
+        EVENT_TYPES.iter().find(|t| t.id == id)
+    }
+
+    /// Creates an event of this type. The payload must contain exactly the fields of this type.
+    pub fn event(&self, payload: &Map<String, Value>) -> Result<Event, PayloadError> {
+        if let Some(key) = payload.keys().find(|key| !self.fields.iter().any(|(f, _)| f == key)) {
+            return Err(PayloadError::UnknownField(key.clone()));
+        }
+
+        let mut bytes = Vec::new();
+        for &(field, field_type) in self.fields {
+            let value = payload.get(field).ok_or(PayloadError::MissingField(field))?;
+            let maximum = field_type.maximum();
+            let value = value
+                .as_u64()
+                .filter(|v| *v <= maximum)
+                .ok_or(PayloadError::InvalidValue { field, maximum })?;
+            bytes.extend(&value.to_le_bytes()[..field_type.size()]);
+        }
+
+        let mut event = ExternalEvent {
+            id: self.id,
+            length: u8::try_from(bytes.len()).expect("payload of EVENT_TYPES is too long"),
+            payload: [0; MAXIMUM_EXTERNAL_PAYLOAD],
+        };
+        event.payload[..bytes.len()].copy_from_slice(&bytes);
+        Ok(Event::External(event))
+    }
+
+    fn payload_size(&self) -> usize {
+        self.fields.iter().map(|(_, t)| t.size()).sum()
+    }
+}
+
+/// An event of one of the `EVENT_TYPES`, with its payload encoded as it is sent to the COBC
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExternalEvent {
+    pub id: u8,
+    length: u8,
+    payload: [u8; MAXIMUM_EXTERNAL_PAYLOAD],
+}
+
+impl ExternalEvent {
+    #[must_use]
+    pub fn event_type(&self) -> Option<&'static EventType> {
+        EventType::by_id(self.id)
+    }
+
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..usize::from(self.length)]
+    }
+}
+
+/// Decodes an event as it is sent to the COBC, if it is of one of the `EVENT_TYPES`
+impl TryFrom<&[u8]> for ExternalEvent {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (&id, payload) = bytes.split_first().ok_or(())?;
+        let event_type = EventType::by_id(id).ok_or(())?;
+        if payload.len() != event_type.payload_size() {
+            return Err(());
+        }
+
+        let mut event = Self {
+            id,
+            length: u8::try_from(payload.len()).map_err(|_| ())?,
+            payload: [0; MAXIMUM_EXTERNAL_PAYLOAD],
+        };
+        event.payload[..payload.len()].copy_from_slice(payload);
+        Ok(event)
+    }
+}
+
+/// The name of the type, followed by the fields of the payload, e.g. `radiation/threshold level=3`
+impl Display for ExternalEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(event_type) = self.event_type() else {
+            return write!(f, "unknown event {} {:02x?}", self.id, self.payload());
+        };
+
+        write!(f, "{}", event_type.name)?;
+        let mut payload = self.payload();
+        for (field, field_type) in event_type.fields {
+            let Some((value, rest)) = payload.split_at_checked(field_type.size()) else { break };
+            let mut bytes = [0; 8];
+            bytes[..value.len()].copy_from_slice(value);
+            write!(f, " {field}={}", u64::from_le_bytes(bytes))?;
+            payload = rest;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const THRESHOLD: EventType = EventType {
+        name: "radiation/threshold",
+        id: 200,
+        fields: &[("level", FieldType::U8), ("dose", FieldType::U32)],
+        group: None,
+    };
+
+    fn payload(value: Value) -> Map<String, Value> {
+        let Value::Object(map) = value else { panic!("expected an object") };
+        map
+    }
+
+    #[test]
+    fn registry_is_consistent() {
+        for (i, event_type) in EVENT_TYPES.iter().enumerate() {
+            assert!(
+                ![0, 1, 2, 5, 6].contains(&event_type.id),
+                "{} uses a reserved id",
+                event_type.name
+            );
+            assert!(
+                event_type.payload_size() <= MAXIMUM_EXTERNAL_PAYLOAD,
+                "{} has a too long payload",
+                event_type.name
+            );
+            for other in &EVENT_TYPES[i + 1..] {
+                assert_ne!(event_type.name, other.name);
+                assert_ne!(event_type.id, other.id);
+            }
+        }
+    }
+
+    #[test]
+    fn payload_is_checked_and_encoded() {
+        let Event::External(event) =
+            THRESHOLD.event(&payload(json!({ "level": 3, "dose": 258 }))).unwrap()
+        else {
+            panic!("expected an external event")
+        };
+        assert_eq!(event.payload(), [3, 2, 1, 0, 0]);
+
+        assert_eq!(
+            THRESHOLD.event(&payload(json!({ "level": 3 }))),
+            Err(PayloadError::MissingField("dose"))
+        );
+        assert_eq!(
+            THRESHOLD.event(&payload(json!({ "level": 256, "dose": 0 }))),
+            Err(PayloadError::InvalidValue { field: "level", maximum: 255 })
+        );
+        assert_eq!(
+            THRESHOLD.event(&payload(json!({ "level": 1, "dose": 0, "unit": 2 }))),
+            Err(PayloadError::UnknownField("unit".into()))
+        );
+    }
+
+    #[test]
+    fn events_are_decoded() {
+        let event = ExternalEvent::try_from([3].as_slice()).unwrap();
+        assert_eq!(event.to_string(), "dosimeter/on");
+        assert_eq!(Vec::from(Event::External(event)), [3]);
+        assert!(ExternalEvent::try_from([4, 0].as_slice()).is_err());
+        assert!(ExternalEvent::try_from([1].as_slice()).is_err());
+    }
+}
@@ -1,24 +1,30 @@
-use super::{CommandError, CommandResult, SyncExecutionContext};
+use super::{program_store::StoreError, CommandError, CommandResult, SyncExecutionContext};
 use crate::{
     command::{
-        check_length, terminate_student_program, Event, ProgramStatus, ResultId, RetryEvent,
+        check_length, common::terminate_locked, kill_student_group, send_nack, Event, NackReason,
+        ProgramStatus, ProgramStore, ResultId, RetryEvent, STORE_ENV,
     },
     communication::{CEPPacket, CommunicationHandle},
+    config::Configuration,
+    logging,
+    student_api::{self, ApiSession},
+    watchdog::WatchdogHandle,
 };
 use anyhow::anyhow;
 use simple_archive::Compression;
 use std::{
     io::{ErrorKind, Write},
-    path::{Path, PathBuf},
+    path::Path,
     time::Duration,
 };
 use subprocess::Popen;
 
-const MAXIMUM_FILE_SIZE: usize = 1_000_000;
+/// How long the processes of a student program may take to exit once they are killed
+const KILL_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Executes a students program and starts a watchdog for it. The watchdog also creates entries in the
 /// status and result queue found in `context`. The result, including logs, is packed into
-/// `./data/{program_id}_{timestamp}`
+/// `{data_path}/{program_id}_{timestamp}`
 pub fn execute_program(
     data: &[u8],
     com: &mut impl CommunicationHandle,
@@ -29,98 +35,189 @@ pub fn execute_program(
     let program_id = u16::from_le_bytes([data[1], data[2]]);
     let timestamp = u32::from_le_bytes([data[3], data[4], data[5], data[6]]);
     let timeout = Duration::from_secs(u16::from_le_bytes([data[7], data[8]]).into());
+
+    if let Err((reason, e)) = start_program(exec, program_id, timestamp, timeout) {
+        return send_nack(com, reason, e);
+    }
+
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
+
+/// Stops the running program and starts the given one, see `execute_program`. On failure, the
+/// reason to report to the COBC is returned along with the error.
+pub fn start_program(
+    exec: &mut SyncExecutionContext,
+    program_id: u16,
+    timestamp: u32,
+    timeout: Duration,
+) -> Result<(), (NackReason, CommandError)> {
+    let lifecycle = exec.lock().unwrap().lifecycle.clone();
+    let _lifecycle = lifecycle.lock().unwrap();
     log::info!("Executing Program {}:{} for {}s", program_id, timestamp, timeout.as_secs());
 
-    terminate_student_program(exec).expect("to terminate a running program");
+    terminate_locked(exec)?;
 
-    let student_process = match create_student_process(program_id, timestamp) {
-        Ok(p) => p,
-        Err(e) => {
-            com.send_packet(&CEPPacket::Nack)?;
-            return Err(e);
-        }
-    };
+    let config = exec.lock().unwrap().config.clone();
+    if !config.program_path(program_id).join("main.py").exists() {
+        return Err((
+            NackReason::ProgramNotFound,
+            CommandError::ProtocolViolation(anyhow!("Could not find matching program")),
+        ));
+    }
+
+    let store = ProgramStore::new(&config, program_id);
+    let store_path = store.checkout().map_err(|e| (NackReason::Storage, e.into()))?;
+    let session = ApiSession::new(program_id, timestamp)
+        .map_err(|e| (NackReason::ProgramNotStarted, e.into()))?;
+    let student_process =
+        create_student_process(&config, program_id, timestamp, &store_path, &session.token)
+            .map_err(|e| (NackReason::ProgramNotStarted, e))?;
+    let student_pid = student_process.pid();
+    logging::start_excerpt();
+
+    // The program is marked as running before its supervisor starts, so that the supervisor can
+    // not finish and clean up before the state is set
+    let mut l_context = exec.lock().unwrap();
+    l_context.run += 1;
+    let run = l_context.run;
+    l_context.running_flag = true;
+    l_context.api_session = Some(session);
+    l_context.student_pid = student_pid;
+    l_context.paused = false;
 
     // WATCHDOG THREAD
     let mut wd_context = exec.clone();
+    let check_in = l_context.watchdog.register("supervisor", config.watchdog_timeout());
     let wd_handle = std::thread::spawn(move || {
-        let exit_code = supervise_process(student_process, timeout, &mut wd_context).unwrap_or(255);
+        let exit_code =
+            supervise_process(student_process, timeout, &mut wd_context, &check_in, &store, run)
+                .unwrap_or(255);
+        // compressing a large result may legitimately take longer than the watchdog timeout
+        drop(check_in);
+
+        if wd_context.lock().unwrap().run != run {
+            log::warn!("Supervisor of program {program_id}:{timestamp} was abandoned, exiting");
+            return;
+        }
 
         log::info!("Program {}:{} finished with {}", program_id, timestamp, exit_code);
+        if let Err(e) = store.commit() {
+            log::error!("Discarded changes to the store of program {program_id}: {e}");
+        }
+        let excerpt = logging::take_excerpt();
         let sid = ProgramStatus { program_id, timestamp, exit_code };
         let rid = ResultId { program_id, timestamp };
-        build_result_archive(rid).unwrap(); // create the tar file with result and log
+        // create the tar file with result and log
+        let result_built = build_result_archive(&config, rid, &excerpt)
+            .map_err(|e| log::error!("Could not build result archive for {rid}: {e}"))
+            .is_ok();
 
         let mut context = wd_context.lock().unwrap();
-        context.event_vec.push(RetryEvent::new(Event::Status(sid))).unwrap();
-        context.event_vec.push(RetryEvent::new(Event::Result(rid))).unwrap();
+        if context.run != run {
+            log::warn!("Supervisor of program {program_id}:{timestamp} was abandoned, exiting");
+            return;
+        }
+        let tries = config.event_send_tries;
+        context.events.push(RetryEvent::new(Event::Status(sid), tries)).unwrap();
+        if result_built {
+            context.events.push(RetryEvent::new(Event::Result(rid), tries)).unwrap();
+        }
         context.running_flag = false;
-        context.update_pin.set_high();
+        context.api_session = None;
+        context.student_pid = None;
+        context.paused = false;
         drop(context);
     });
 
-    // After spawning the watchdog thread, store its handle and set flag
-    let mut l_context = exec.lock().unwrap();
     l_context.thread_handle = Some(wd_handle);
-    l_context.running_flag = true;
     drop(l_context);
 
-    com.send_packet(&CEPPacket::Ack)?;
     Ok(())
 }
 
-/// This function creates and executes a student process. Its stdout/stderr is written into
-/// `./data/[program_id]_[timestamp].log`
-fn create_student_process(program_id: u16, timestamp: u32) -> Result<Popen, CommandError> {
-    let program_path = format!("./archives/{program_id}/main.py");
-    if !Path::new(&program_path).exists() {
-        return Err(CommandError::ProtocolViolation(anyhow!("Could not find matching program")));
-    }
-
+/// This function creates and executes a student process in its own process group, so that it can
+/// be paused along with its children. Its stdout/stderr is written into
+/// `{data_path}/[program_id]_[timestamp].log`. The directory of its store is passed in `EDU_STORE`,
+/// the path of the API socket and the token it authenticates with in `EDU_API_SOCKET` and
+/// `EDU_API_TOKEN`.
+fn create_student_process(
+    config: &Configuration,
+    program_id: u16,
+    timestamp: u32,
+    store_path: &Path,
+    token: &str,
+) -> Result<Popen, CommandError> {
     // TODO run the program from a student user (setuid)
-    let output_file = std::fs::File::create(format!("./data/{program_id}_{timestamp}.log"))?; // will contain the stdout and stderr of the execute program
-    let config = subprocess::PopenConfig {
-        cwd: Some(format!("./archives/{program_id}").into()),
+    let output_file =
+        std::fs::File::create(config.data_file(format!("{program_id}_{timestamp}.log")))?; // will contain the stdout and stderr of the execute program
+    let mut env = subprocess::PopenConfig::current_env();
+    env.push((STORE_ENV.into(), store_path.into()));
+    env.push((student_api::SOCKET_ENV.into(), std::path::absolute(&config.api_socket)?.into()));
+    env.push((student_api::TOKEN_ENV.into(), token.into()));
+    let popen_config = subprocess::PopenConfig {
+        cwd: Some(config.program_path(program_id).into()),
         detached: false, // do not spawn as separate process
         stdout: subprocess::Redirection::File(output_file),
         stderr: subprocess::Redirection::Merge,
+        env: Some(env),
+        setpgid: true,
         ..Default::default()
     };
 
-    let process = Popen::create(&["python", "main.py", &timestamp.to_string()], config)?;
+    let process =
+        Popen::create(&[config.python.as_str(), "main.py", &timestamp.to_string()], popen_config)?;
     Ok(process)
 }
 
 /// A function intended to be run in a separate process, which checks every seconds if the given
 /// timeout has passed or the process terminated itself. If it didnt, the process is killed.
+/// Processes the student program started are killed in any case, so none of them outlives it.
 fn supervise_process(
     mut process: Popen,
     timeout: Duration,
     exec: &mut SyncExecutionContext,
+    check_in: &WatchdogHandle,
+    store: &ProgramStore,
+    run: u64,
 ) -> Result<u8, ()> {
-    if let Ok(code) = run_until_timeout(&mut process, timeout, exec) {
-        Ok(code)
-    } else {
+    let pid = process.pid().expect("student process was just started");
+    let result = run_until_timeout(&mut process, timeout, exec, check_in, store, run);
+    if result.is_err() {
         log::warn!("Student Process timed out or is stopped");
-        process.kill().unwrap(); // send SIGKILL
-        process
-            .wait_timeout(Duration::from_millis(200)) // wait for it to do its magic
-            .unwrap()
-            .unwrap(); // Panic if not stopped
-        Err(())
     }
+
+    let context = exec.lock().unwrap();
+    let paused = context.run == run && context.paused;
+    drop(context);
+    if let Err(e) = kill_student_group(pid, paused, KILL_TIMEOUT) {
+        log::error!("Could not kill the processes of the student program: {e}");
+    }
+    process
+        .wait_timeout(KILL_TIMEOUT) // wait for it to do its magic
+        .unwrap()
+        .unwrap(); // Panic if not stopped
+    result
 }
 
-/// This function allows the program to run for timeout (rounded to seconds)
-/// If the program terminates, it exit code is returned
-/// If it times out or the running flag is reset, an Err is returned instead
+/// This function allows the program to run for timeout (rounded to seconds), not counting the time
+/// it is paused.
+/// If the program terminates, it exit code is returned. If it asked for its result to be packed
+/// through the API socket, 0 is returned and it is killed by `supervise_process`.
+/// If it times out, the running flag is reset, its store grows beyond the quota or the supervisor
+/// was abandoned, an Err is returned instead
 fn run_until_timeout(
     process: &mut Popen,
     timeout: Duration,
     exec: &mut SyncExecutionContext,
+    check_in: &WatchdogHandle,
+    store: &ProgramStore,
+    run: u64,
 ) -> Result<u8, ()> {
     // Loop over timeout in 1s steps
-    for _ in 0..timeout.as_secs() {
+    let mut remaining = timeout.as_secs();
+    while remaining > 0 {
+        check_in.check_in();
         if let Some(status) = process // if student program terminates with exit code
             .wait_timeout(Duration::from_secs(1))
             .unwrap()
@@ -131,35 +228,62 @@ fn run_until_timeout(
             }
             return Ok(0);
         }
+        if let Err(e @ StoreError::QuotaExceeded { .. }) = store.check_work_size() {
+            log::error!("Stopping student program: {e}");
+            break;
+        }
 
-        if !exec.lock().unwrap().running_flag {
+        let context = exec.lock().unwrap();
+        if context.run != run || !context.running_flag {
             // if student program should be stopped
             break;
         }
+        if context.api_session.as_ref().is_some_and(|s| s.package_requested) {
+            return Ok(0);
+        }
+        if !context.paused {
+            remaining -= 1;
+        }
     }
 
     Err(())
 }
 
 /// The function uses `tar` to create an uncompressed archive that includes the result file specified, as well as
-/// the programs stdout/stderr and the schedulers log file. If any of the files is missing, the archive
-/// is created without them.
-fn build_result_archive(res: ResultId) -> Result<(), std::io::Error> {
-    let out_path = PathBuf::from(&format!("./data/{res}"));
+/// the programs stdout/stderr and the lines the scheduler logged during the execution. If any of the
+/// files is missing, the archive is created without them.
+fn build_result_archive(
+    config: &Configuration,
+    res: ResultId,
+    log_excerpt: &[u8],
+) -> Result<(), std::io::Error> {
+    let out_path = config.data_file(res);
     let mut archive = simple_archive::Writer::new(std::fs::File::create(out_path)?);
 
-    let res_path =
-        PathBuf::from(format!("./archives/{}/results/{}", res.program_id, res.timestamp));
-    let student_log_path = PathBuf::from(format!("./data/{res}.log"));
-    let log_path = PathBuf::from("./log");
+    let res_path = config.program_path(res.program_id).join(format!("results/{}", res.timestamp));
+    let student_log_path = config.data_file(format!("{res}.log"));
+    let max_size = config.maximum_file_size;
 
-    add_to_archive_if_exists(&mut archive, &res.to_string(), &res_path, Compression::None)?;
-    add_to_archive_if_exists(&mut archive, "student_log", &student_log_path, Compression::Zopfli)?;
-    add_to_archive_if_exists(&mut archive, "log", &log_path, Compression::Zopfli)?;
+    add_to_archive_if_exists(
+        &mut archive,
+        &res.to_string(),
+        &res_path,
+        Compression::None,
+        max_size,
+    )?;
+    add_to_archive_if_exists(
+        &mut archive,
+        "student_log",
+        &student_log_path,
+        Compression::Zopfli,
+        max_size,
+    )?;
+    if !log_excerpt.is_empty() {
+        archive.append_data("log", log_excerpt, Compression::Zopfli)?;
+    }
 
     let _ = std::fs::remove_file(res_path);
     let _ = std::fs::remove_file(student_log_path);
-    let _ = std::fs::OpenOptions::new().write(true).truncate(true).open(log_path);
 
     Ok(())
 }
@@ -169,10 +293,11 @@ fn add_to_archive_if_exists<T: Write>(
     name: &str,
     path: impl AsRef<Path>,
     compression: simple_archive::Compression,
+    max_size: usize,
 ) -> std::io::Result<()> {
     match std::fs::read(path) {
         Ok(mut data) => {
-            data.truncate(MAXIMUM_FILE_SIZE);
+            data.truncate(max_size);
             archive.append_data(name, &data, compression)?;
             Ok(())
         }
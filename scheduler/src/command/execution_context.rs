@@ -1,13 +1,16 @@
-use filevec::FileVec;
+use super::{
+    EventNotifier, EventQueue, EventType, ExternalEvent, FaultLog, FileNotifier, GpioNotifier,
+    MemoryNotifier, RecoveryPolicy,
+};
+use crate::{
+    config::Configuration, gpio::GpioBackend, student_api::ApiSession, watchdog::Watchdog,
+};
 use std::{
     fmt::Display,
-    str::FromStr,
     sync::{Arc, Mutex},
     thread,
 };
 
-const EVENT_SEND_TRIES: u32 = 5;
-
 /// This type makes the `ExecutionContext` thread-safe
 pub type SyncExecutionContext = Arc<Mutex<ExecutionContext>>;
 
@@ -15,42 +18,80 @@ pub type SyncExecutionContext = Arc<Mutex<ExecutionContext>>;
 pub struct ExecutionContext {
     /// Contains the `JoinHandle` of the watchdog thread
     pub thread_handle: Option<thread::JoinHandle<()>>,
+    /// Identifies the current run of a student program. It changes whenever a program is started
+    /// or its supervisor is abandoned, so that a supervisor only touches the state of its own run.
+    pub run: u64,
     /// Through this value, the watchdog thread indicates, wether a student program is currently
     /// running. Changing it from true to false, indicates to the watchdog thread, that the
     /// program should be stopped
     pub running_flag: bool,
-    /// This integer is the pin number of the `EDU_Update` pin
-    pub update_pin: UpdatePin,
-    /// Vector containing events that should be sent to the COBC
-    pub event_vec: FileVec<RetryEvent<Event>>,
+    /// Events that should be sent to the COBC, signalled through the `EDU_Update` pin
+    pub events: EventQueue,
+    /// Decides when the scheduler has to be restarted after faults
+    pub recovery: RecoveryPolicy,
+    /// Persistent record of all failed commands, retrievable by the COBC
+    pub fault_log: FaultLog,
+    /// The configuration the scheduler was started with
+    pub config: Configuration,
+    /// Monitors the critical threads, the heartbeat stops if one of them goes silent, see
+    /// `watchdog::Heartbeat`
+    pub watchdog: Watchdog,
+    /// The execution that may currently use the API socket, see `student_api::respond`
+    pub api_session: Option<ApiSession>,
+    /// The process id of the running student program, which is also the id of its process group
+    pub student_pid: Option<u32>,
+    /// Wether the running student program is stopped by Pause Program
+    pub paused: bool,
+    /// Held while a program is started or stopped, as that happens without holding the context
+    /// itself. Programs may be started and stopped by the COBC and through the control socket at
+    /// the same time.
+    pub lifecycle: Arc<Mutex<()>>,
 }
 
 impl ExecutionContext {
+    /// Signals pending events through the `EDU_Update` pin of the given backend, or the file
+    /// `update_pin_file` if one is configured. If the pin is not available, the COBC has to poll
+    /// with Get Status.
     pub fn new(
-        event_file_path: String,
-        update_pin: u8,
+        config: Configuration,
+        gpio: &dyn GpioBackend,
+    ) -> Result<Arc<Mutex<Self>>, std::io::Error> {
+        let notifier: Box<dyn EventNotifier> = if config.update_pin_file.as_os_str().is_empty() {
+            match gpio.output(config.update_pin) {
+                Ok(pin) => Box::new(GpioNotifier::new(pin)),
+                Err(e) => {
+                    log::error!("Could not open update pin, events are not signalled: {e}");
+                    Box::new(MemoryNotifier::default())
+                }
+            }
+        } else {
+            Box::new(FileNotifier::new(&config.update_pin_file))
+        };
+        Self::with_notifier(config, notifier)
+    }
+
+    pub fn with_notifier(
+        config: Configuration,
+        notifier: Box<dyn EventNotifier>,
     ) -> Result<Arc<Mutex<Self>>, std::io::Error> {
-        let mut ec = ExecutionContext {
+        let ec = ExecutionContext {
             thread_handle: None,
+            run: 0,
             running_flag: false,
-            update_pin: UpdatePin::new(update_pin),
-            event_vec: FileVec::open(event_file_path)?,
+            events: EventQueue::open(&config.events_path, notifier)?,
+            recovery: RecoveryPolicy::default(),
+            fault_log: FaultLog::open(&config.fault_log_path)?,
+            config,
+            watchdog: Watchdog::default(),
+            api_session: None,
+            student_pid: None,
+            paused: false,
+            lifecycle: Arc::default(),
         };
 
-        ec.configure_update_pin();
-
         Ok(Arc::new(Mutex::new(ec)))
     }
 
-    /// Checks and sets/resets the update pin accordingly
-    pub fn configure_update_pin(&mut self) {
-        if self.has_data_ready() {
-            self.update_pin.set_high();
-        } else {
-            self.update_pin.set_low();
-        }
-    }
-
     #[must_use]
     pub fn is_student_program_running(&self) -> bool {
         self.thread_handle.is_some()
@@ -58,50 +99,7 @@ impl ExecutionContext {
 
     #[must_use]
     pub fn has_data_ready(&self) -> bool {
-        !self.event_vec.as_ref().is_empty()
-    }
-}
-
-#[cfg(not(feature = "mock"))]
-pub struct UpdatePin {
-    pub pin: rppal::gpio::OutputPin,
-}
-
-#[cfg(not(feature = "mock"))]
-impl UpdatePin {
-    #[must_use]
-    pub fn new(pin: u8) -> Self {
-        let mut update_pin =
-            UpdatePin { pin: rppal::gpio::Gpio::new().unwrap().get(pin).unwrap().into_output() };
-        update_pin.pin.set_reset_on_drop(false);
-        update_pin
-    }
-
-    pub fn set_high(&mut self) {
-        self.pin.set_high();
-    }
-
-    pub fn set_low(&mut self) {
-        self.pin.set_low();
-    }
-}
-
-/// This impl is only used when doing tests without hardware
-#[cfg(feature = "mock")]
-pub struct UpdatePin;
-
-#[cfg(feature = "mock")]
-impl UpdatePin {
-    pub fn new(_pin: u8) -> Self {
-        Self
-    }
-
-    pub fn set_high(&mut self) {
-        let _ = std::fs::write("updatepin", b"");
-    }
-
-    pub fn set_low(&mut self) {
-        let _ = std::fs::remove_file("updatepin");
+        !self.events.is_empty()
     }
 }
 
@@ -126,14 +124,101 @@ impl Display for ResultId {
     }
 }
 
+/// Reported when the running student program is paused or resumed
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ProgramState {
+    pub program_id: u16,
+    pub timestamp: u32,
+    pub paused: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(from = "StoredEvent")]
 pub enum Event {
     Status(ProgramStatus),
     Result(ResultId),
+    State(ProgramState),
+    /// Queued by another process on the EDU, see `EVENT_TYPES`
+    External(ExternalEvent),
+    Custom(CustomEvent),
+}
+
+/// An `Event` as it may be found in the persisted queue. Earlier versions had dedicated variants
+/// for the dosimeter, which are read as the external events that replaced them.
+#[derive(serde::Deserialize)]
+enum StoredEvent {
+    Status(ProgramStatus),
+    Result(ResultId),
+    State(ProgramState),
+    External(ExternalEvent),
+    Custom(CustomEvent),
     EnableDosimeter,
     DisableDosimeter,
 }
 
+impl From<StoredEvent> for Event {
+    fn from(event: StoredEvent) -> Self {
+        let external = |name| {
+            EventType::by_name(name)
+                .and_then(|t| t.event(&serde_json::Map::new()).ok())
+                .expect("dosimeter events are registered without payload")
+        };
+
+        match event {
+            StoredEvent::Status(s) => Event::Status(s),
+            StoredEvent::Result(r) => Event::Result(r),
+            StoredEvent::State(s) => Event::State(s),
+            StoredEvent::External(e) => Event::External(e),
+            StoredEvent::Custom(c) => Event::Custom(c),
+            StoredEvent::EnableDosimeter => external("dosimeter/on"),
+            StoredEvent::DisableDosimeter => external("dosimeter/off"),
+        }
+    }
+}
+
+impl Event {
+    /// Whether the COBC only needs this event if it directly follows `other`, which is the case
+    /// for external events of the same group
+    #[must_use]
+    pub fn supersedes(&self, other: &Event) -> bool {
+        let group = |e: &Event| match e {
+            Event::External(e) => e.event_type().and_then(|t| t.group),
+            _ => None,
+        };
+        group(self).is_some() && group(self) == group(other)
+    }
+}
+
+/// The maximum number of bytes a student program can attach to a custom event
+pub const MAXIMUM_CUSTOM_PAYLOAD: usize = 16;
+
+/// An event a running student program queued through the API socket
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CustomEvent {
+    pub program_id: u16,
+    length: u8,
+    payload: [u8; MAXIMUM_CUSTOM_PAYLOAD],
+}
+
+impl CustomEvent {
+    /// Returns `None` if the payload is longer than `MAXIMUM_CUSTOM_PAYLOAD`
+    #[must_use]
+    pub fn new(program_id: u16, payload: &[u8]) -> Option<Self> {
+        let mut event = Self {
+            program_id,
+            length: u8::try_from(payload.len()).ok()?,
+            payload: [0; MAXIMUM_CUSTOM_PAYLOAD],
+        };
+        event.payload.get_mut(..payload.len())?.copy_from_slice(payload);
+        Some(event)
+    }
+
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..usize::from(self.length)]
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct RetryEvent<T> {
     pub retries: u32,
@@ -141,8 +226,9 @@ pub struct RetryEvent<T> {
 }
 
 impl<T> RetryEvent<T> {
-    pub fn new(event: T) -> Self {
-        Self { retries: EVENT_SEND_TRIES, event }
+    /// The event is sent at most `retries` times before it is dropped
+    pub fn new(event: T, retries: u32) -> Self {
+        Self { retries, event }
     }
 }
 
@@ -161,25 +247,39 @@ impl From<Event> for Vec<u8> {
                 v.extend(r.program_id.to_le_bytes());
                 v.extend(r.timestamp.to_le_bytes());
             }
-            Event::EnableDosimeter => {
-                v.push(3);
+            Event::State(s) => {
+                v.push(6);
+                v.extend(s.program_id.to_le_bytes());
+                v.extend(s.timestamp.to_le_bytes());
+                v.push(u8::from(s.paused));
             }
-            Event::DisableDosimeter => {
-                v.push(4);
+            Event::External(e) => {
+                v.push(e.id);
+                v.extend(e.payload());
+            }
+            Event::Custom(c) => {
+                v.push(5);
+                v.extend(c.program_id.to_le_bytes());
+                v.extend(c.payload());
             }
         }
         v
     }
 }
 
-impl FromStr for Event {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "dosimeter/on" => Ok(Event::EnableDosimeter),
-            "dosimeter/off" => Ok(Event::DisableDosimeter),
-            _ => Err(()),
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Status(s) => {
+                write!(f, "status of {}_{}: exit code {}", s.program_id, s.timestamp, s.exit_code)
+            }
+            Event::Result(r) => write!(f, "result {r}"),
+            Event::State(s) => {
+                let state = if s.paused { "paused" } else { "resumed" };
+                write!(f, "state of {}_{}: {state}", s.program_id, s.timestamp)
+            }
+            Event::External(e) => write!(f, "{e}"),
+            Event::Custom(c) => write!(f, "custom of {}: {:02x?}", c.program_id, c.payload()),
         }
     }
 }
@@ -0,0 +1,237 @@
+use super::{recovery::Fault, CommandError};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// A single entry of the [`FaultLog`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FaultRecord {
+    /// Seconds since the unix epoch
+    pub timestamp: u32,
+    /// Id of the command during which the fault happened, 0 if none was received yet
+    pub command: u8,
+    /// See `CommandError::class`
+    pub class: u8,
+    /// The [`Fault`] classification for non-recoverable errors, 0 otherwise
+    pub fault: u8,
+    /// See `CommandError::detail`
+    pub detail: u16,
+}
+
+impl FaultRecord {
+    pub const SERIALIZED_LENGTH: usize = 9;
+
+    #[must_use]
+    pub fn new(command: u8, error: &CommandError) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| u32::try_from(d.as_secs()).unwrap_or(u32::MAX));
+        let fault = match error {
+            CommandError::NonRecoverable(e) => Fault::classify(e) as u8,
+            _ => 0,
+        };
+
+        Self { timestamp, command, class: error.class(), fault, detail: error.detail() }
+    }
+
+    #[must_use]
+    pub fn serialize(&self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let mut bytes = [0; Self::SERIALIZED_LENGTH];
+        bytes[0..4].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes[4] = self.command;
+        bytes[5] = self.class;
+        bytes[6] = self.fault;
+        bytes[7..9].copy_from_slice(&self.detail.to_le_bytes());
+        bytes
+    }
+
+    fn deserialize(bytes: &[u8]) -> Self {
+        Self {
+            timestamp: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            command: bytes[4],
+            class: bytes[5],
+            fault: bytes[6],
+            detail: u16::from_le_bytes([bytes[7], bytes[8]]),
+        }
+    }
+}
+
+/// A bounded, persistent log of faults and anomalies. The records are kept in a ring buffer, so
+/// once `CAPACITY` is reached, the oldest record is overwritten.
+///
+/// The file starts with the index of the oldest record and the number of records (both u16),
+/// followed by `CAPACITY` slots of `FaultRecord::SERIALIZED_LENGTH` bytes.
+pub struct FaultLog {
+    file: File,
+    /// Slot index of the oldest record
+    head: usize,
+    records: VecDeque<FaultRecord>,
+}
+
+impl FaultLog {
+    pub const CAPACITY: usize = 256;
+    const HEADER_LENGTH: usize = 4;
+    const FILE_LENGTH: usize =
+        Self::HEADER_LENGTH + Self::CAPACITY * FaultRecord::SERIALIZED_LENGTH;
+
+    /// Opens the fault log stored in the given file, creating it if necessary.
+    ///
+    /// **Note:** If the file exists but is malformed, the log starts out empty.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        let mut log = Self { file, head: 0, records: VecDeque::new() };
+        if buffer.len() != Self::FILE_LENGTH {
+            log.file.set_len(Self::FILE_LENGTH as u64)?;
+            log.write_header()?;
+            return Ok(log);
+        }
+
+        let head = usize::from(u16::from_le_bytes([buffer[0], buffer[1]]));
+        let len = usize::from(u16::from_le_bytes([buffer[2], buffer[3]]));
+        if head >= Self::CAPACITY || len > Self::CAPACITY {
+            log.write_header()?;
+            return Ok(log);
+        }
+
+        log.head = head;
+        log.records = (0..len)
+            .map(|i| {
+                let offset = Self::slot_offset((head + i) % Self::CAPACITY);
+                FaultRecord::deserialize(&buffer[offset..offset + FaultRecord::SERIALIZED_LENGTH])
+            })
+            .collect();
+
+        Ok(log)
+    }
+
+    /// Returns all records, oldest first
+    #[must_use]
+    pub fn records(&self) -> &VecDeque<FaultRecord> {
+        &self.records
+    }
+
+    /// Appends a record, overwriting the oldest one if the log is full
+    pub fn push(&mut self, record: FaultRecord) -> std::io::Result<()> {
+        let slot = (self.head + self.records.len()) % Self::CAPACITY;
+        if self.records.len() == Self::CAPACITY {
+            self.records.pop_front();
+            self.head = (self.head + 1) % Self::CAPACITY;
+        }
+        self.records.push_back(record);
+
+        self.file.seek(SeekFrom::Start(Self::slot_offset(slot) as u64))?;
+        self.file.write_all(&record.serialize())?;
+        self.write_header()
+    }
+
+    /// Removes all records
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.records.clear();
+        self.head = 0;
+        self.write_header()
+    }
+
+    /// Serializes the number of records (u16), followed by the records, oldest first
+    #[must_use]
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut v = u16::try_from(self.records.len()).unwrap().to_le_bytes().to_vec();
+        for record in self.records() {
+            v.extend(record.serialize());
+        }
+        v
+    }
+
+    fn slot_offset(slot: usize) -> usize {
+        Self::HEADER_LENGTH + slot * FaultRecord::SERIALIZED_LENGTH
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&u16::try_from(self.head).unwrap().to_le_bytes())?;
+        self.file.write_all(&u16::try_from(self.records.len()).unwrap().to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: u32) -> FaultRecord {
+        FaultRecord { timestamp, command: 2, class: 3, fault: 1, detail: 28 }
+    }
+
+    #[test]
+    fn records_are_persisted() {
+        let path = "__fault_log_persisted";
+        let _ = std::fs::remove_file(path);
+
+        let mut log = FaultLog::open(path).unwrap();
+        log.push(record(1)).unwrap();
+        log.push(record(2)).unwrap();
+        drop(log);
+
+        let log = FaultLog::open(path).unwrap();
+        assert_eq!(log.records(), &[record(1), record(2)]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn oldest_record_is_overwritten() {
+        let path = "__fault_log_overwritten";
+        let _ = std::fs::remove_file(path);
+
+        let mut log = FaultLog::open(path).unwrap();
+        for i in 0..FaultLog::CAPACITY + 3 {
+            log.push(record(u32::try_from(i).unwrap())).unwrap();
+        }
+        drop(log);
+
+        let log = FaultLog::open(path).unwrap();
+        assert_eq!(log.records().len(), FaultLog::CAPACITY);
+        assert_eq!(log.records()[0], record(3));
+        assert_eq!(log.records()[FaultLog::CAPACITY - 1], record(258));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn cleared_log_is_empty_after_reopening() {
+        let path = "__fault_log_cleared";
+        let _ = std::fs::remove_file(path);
+
+        let mut log = FaultLog::open(path).unwrap();
+        log.push(record(1)).unwrap();
+        log.clear().unwrap();
+        log.push(record(5)).unwrap();
+        drop(log);
+
+        assert_eq!(FaultLog::open(path).unwrap().records(), &[record(5)]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn malformed_file_is_reset() {
+        let path = "__fault_log_malformed";
+        std::fs::write(path, b"garbage").unwrap();
+
+        assert!(FaultLog::open(path).unwrap().records().is_empty());
+        assert_eq!(std::fs::metadata(path).unwrap().len(), FaultLog::FILE_LENGTH as u64);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,41 @@
+use super::{check_length, CommandResult, SyncExecutionContext, SUPPORTED_COMMANDS};
+use crate::communication::{CEPPacket, Capabilities, CommunicationHandle, PROTOCOL_VERSION};
+
+/// Every packet is acknowledged before the next one is sent
+const TRANSFER_STOP_AND_WAIT: u8 = 0x01;
+/// Multiple packets are kept in flight and selectively acknowledged, always without compression
+const TRANSFER_WINDOWED: u8 = 0x02;
+
+/// The git commit hash of this build, or "unknown"
+pub const BUILD_HASH: &str = env!("SCHEDULER_BUILD_HASH");
+
+/// Sends a description of what this scheduler supports, so that the COBC can adapt to it. The
+/// response consists of
+/// * the protocol version (u8)
+/// * the number of supported commands (u8), followed by their ids
+/// * the maximum length of a data packet's payload (u16)
+/// * the supported capabilities, see [`Capabilities`] (u8)
+/// * the supported multi packet transfer modes (u8)
+/// * the length of the build hash (u8), followed by the hash as ASCII
+pub fn get_capabilities(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    _exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    com.send_packet(&CEPPacket::Data(serialize_capabilities()))?;
+    Ok(())
+}
+
+fn serialize_capabilities() -> Vec<u8> {
+    let mut v = vec![PROTOCOL_VERSION];
+    v.push(u8::try_from(SUPPORTED_COMMANDS.len()).unwrap());
+    v.extend(SUPPORTED_COMMANDS);
+    v.extend(u16::try_from(CEPPacket::MAXIMUM_DATA_LENGTH).unwrap().to_le_bytes());
+    v.push(Capabilities::SUPPORTED.0);
+    v.push(TRANSFER_STOP_AND_WAIT | TRANSFER_WINDOWED);
+    v.push(u8::try_from(BUILD_HASH.len()).unwrap());
+    v.extend(BUILD_HASH.as_bytes());
+    v
+}
@@ -0,0 +1,26 @@
+use super::{
+    check_length, send_nack, CommandError, CommandResult, NackReason, SyncExecutionContext,
+};
+use crate::communication::{CEPPacket, CommunicationHandle};
+use anyhow::anyhow;
+
+/// Sends the link statistics of the current session, followed by the lifetime statistics. Each
+/// consists of six u32 counters: sent, received, CRC failures, NACKs, timeouts and retransmits
+pub fn get_com_statistics(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    _exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    let Some(bytes) = com.statistics().map(|s| s.lock().unwrap().serialize()) else {
+        return send_nack(
+            com,
+            NackReason::Unsupported,
+            CommandError::ProtocolViolation(anyhow!("Handle does not keep statistics")),
+        );
+    };
+
+    com.send_packet(&CEPPacket::Data(bytes))?;
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use super::{
+    check_length_between, send_nack, CommandError, CommandResult, NackReason, SyncExecutionContext,
+};
+use crate::communication::{CEPPacket, CommunicationHandle};
+use anyhow::anyhow;
+
+const MAXIMUM_KEY_LENGTH: usize = 64;
+
+/// Sends the configuration the scheduler is running with, formatted as TOML. If a key follows the
+/// command id, only that key is sent.
+pub fn get_config(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length_between(com, data, 1, 1 + MAXIMUM_KEY_LENGTH)?;
+
+    let config = exec.lock().unwrap().config.clone();
+    let response = if data.len() == 1 {
+        toml::to_string(&config).map_err(|e| CommandError::NonRecoverable(e.into()))?
+    } else {
+        let key = String::from_utf8_lossy(&data[1..]);
+        let Some(assignment) = config.get(&key) else {
+            return send_nack(
+                com,
+                NackReason::InvalidParameter,
+                CommandError::ProtocolViolation(anyhow!("Unknown configuration key {key:?}")),
+            );
+        };
+        assignment
+    };
+
+    com.send_packet(&CEPPacket::Data(response.into_bytes()))?;
+    Ok(())
+}
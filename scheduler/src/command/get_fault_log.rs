@@ -0,0 +1,27 @@
+use super::{check_length, CommandError, CommandResult, SyncExecutionContext};
+use crate::communication::{CEPPacket, CommunicationHandle};
+
+/// Sends the number of records in the fault log (u16), followed by the records, oldest first. If
+/// the second byte of the command is 1, the log is cleared once the COBC acknowledged it.
+pub fn get_fault_log(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 2)?;
+    let clear = data[1] == 1;
+
+    let bytes = exec.lock().unwrap().fault_log.serialize();
+    com.send_packet(&CEPPacket::Data(bytes))?;
+
+    if clear {
+        exec.lock()
+            .unwrap()
+            .fault_log
+            .clear()
+            .map_err(|e| CommandError::NonRecoverable(e.into()))?;
+        log::info!("Cleared fault log");
+    }
+
+    Ok(())
+}
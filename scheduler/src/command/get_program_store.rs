@@ -0,0 +1,24 @@
+use super::{
+    check_length, send_nack, CommandResult, NackReason, ProgramStore, SyncExecutionContext,
+};
+use crate::communication::CommunicationHandle;
+
+/// Sends the store of the program with the given id, see `ProgramStore::serialize`
+pub fn get_program_store(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 3)?;
+
+    let program_id = u16::from_le_bytes([data[1], data[2]]);
+    let store = ProgramStore::new(&exec.lock().unwrap().config, program_id);
+    let bytes = match store.serialize() {
+        Ok(bytes) => bytes,
+        Err(e) => return send_nack(com, NackReason::Storage, e.into()),
+    };
+
+    log::info!("Returning store of program {program_id}");
+    com.send_multi_packet(&bytes)?;
+    Ok(())
+}
@@ -16,8 +16,7 @@ pub fn get_status(
         return Ok(());
     }
 
-    let result = {
-        let mut events = l_exec.event_vec.as_mut();
+    let result = l_exec.events.modify(|events| {
         let index = events.iter().position(|x| matches!(x.event, Event::Status(_))).unwrap_or(0);
 
         events[index].retries -= 1;
@@ -26,8 +25,7 @@ pub fn get_status(
             events.remove(index);
         }
         result
-    };
+    });
 
-    l_exec.configure_update_pin();
     Ok(result?)
 }
@@ -0,0 +1,21 @@
+use super::{check_length, CommandResult, SyncExecutionContext};
+use crate::communication::{CEPPacket, CommunicationHandle};
+
+/// Sends the public keys archives have to be signed with, as the number of keys (u8), followed by
+/// the keys with 32 bytes each. If there are none, signatures are not checked.
+pub fn get_trusted_keys(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    let keys = exec.lock().unwrap().config.trusted_keys();
+    let mut v = vec![u8::try_from(keys.len()).unwrap_or(u8::MAX)];
+    for key in keys.iter().take(usize::from(u8::MAX)) {
+        v.extend(key.as_bytes());
+    }
+
+    com.send_packet(&CEPPacket::Data(v))?;
+    Ok(())
+}
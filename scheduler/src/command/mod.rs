@@ -1,40 +1,144 @@
+mod activate_update;
+mod clear_program_store;
 mod common;
 mod error;
+mod event_notifier;
+mod event_queue;
+mod event_types;
 mod execute_program;
 mod execution_context;
+mod fault_log;
+mod get_capabilities;
+mod get_com_statistics;
+mod get_config;
+mod get_fault_log;
+mod get_program_store;
 mod get_status;
+mod get_trusted_keys;
+mod negotiate_capabilities;
+mod pause_program;
+mod program_store;
+mod recovery;
+mod resume_program;
+mod return_log;
 mod return_result;
+mod set_config;
+mod set_log_level;
 mod stop_program;
 mod store_archive;
+mod store_update;
 mod update_time;
 
-use crate::communication::{CEPPacket, CommunicationHandle};
+use crate::communication::{CEPPacket, Capabilities, CommunicationHandle};
+use activate_update::activate_update;
 use anyhow::anyhow;
+use clear_program_store::clear_program_store;
 pub use common::*;
-pub use error::CommandError;
+pub use error::{CommandError, NackReason};
+pub use event_notifier::*;
+pub use event_queue::EventQueue;
+pub use event_types::*;
 use execute_program::execute_program;
+pub use execute_program::start_program;
 pub use execution_context::*;
+pub use fault_log::{FaultLog, FaultRecord};
+use get_capabilities::get_capabilities;
+pub use get_capabilities::BUILD_HASH;
+use get_com_statistics::get_com_statistics;
+use get_config::get_config;
+use get_fault_log::get_fault_log;
+use get_program_store::get_program_store;
 use get_status::get_status;
+use get_trusted_keys::get_trusted_keys;
+use negotiate_capabilities::negotiate_capabilities;
+use pause_program::pause_program;
+pub use program_store::{recover_stores, ProgramStore, STORE_ENV};
+pub use recovery::{RecoveryPolicy, SupervisorError};
+use resume_program::resume_program;
+use return_log::return_log;
 use return_result::return_result;
-use std::time::Duration;
+use set_config::set_config;
+use set_log_level::set_log_level;
 use stop_program::stop_program;
 use store_archive::store_archive;
+use store_update::store_update;
 use update_time::update_time;
 
-const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
-
 type CommandResult = Result<(), CommandError>;
 
+/// Declares the handler of every command id. Both `SUPPORTED_COMMANDS` and the dispatch in
+/// `process_command` are generated from this table, so they can not diverge.
+macro_rules! commands {
+    ($($id:literal => $handler:ident,)*) => {
+        /// The ids of all commands handled by `process_command`
+        pub const SUPPORTED_COMMANDS: &[u8] = &[$($id),*];
+
+        /// Runs the handler of the command in `data`, or returns `None` if the id is unknown
+        fn dispatch(
+            data: &[u8],
+            com: &mut impl CommunicationHandle,
+            exec: &mut SyncExecutionContext,
+        ) -> Option<CommandResult> {
+            match data[0] {
+                $($id => Some($handler(data, com, exec)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+commands! {
+    0x01 => store_archive,
+    0x02 => execute_program,
+    0x03 => stop_program,
+    0x04 => get_status,
+    0x05 => return_result,
+    0x06 => update_time,
+    0x07 => get_com_statistics,
+    0x08 => negotiate_capabilities,
+    0x09 => get_capabilities,
+    0x0A => get_fault_log,
+    0x0B => return_log,
+    0x0C => set_log_level,
+    0x0D => get_config,
+    0x0E => set_config,
+    0x0F => store_update,
+    0x10 => activate_update,
+    0x11 => get_trusted_keys,
+    0x12 => get_program_store,
+    0x13 => clear_program_store,
+    0x14 => pause_program,
+    0x15 => resume_program,
+}
+
 /// Main routine. Waits for a command to be received from the COBC, then parses and executes it.
-pub fn handle_command(com: &mut impl CommunicationHandle, exec: &mut SyncExecutionContext) {
-    let ret = process_command(com, exec);
+/// Every error is recorded in the fault log. Non-recoverable errors are handed to the recovery
+/// policy, which only restarts the scheduler as a last resort.
+///
+/// Returns wether the command was executed successfully.
+pub fn handle_command(com: &mut impl CommunicationHandle, exec: &mut SyncExecutionContext) -> bool {
+    let mut command = 0;
+    let ret = receive_command(com).and_then(|data| {
+        command = data[0];
+        process_command(&data, com, exec)
+    });
 
-    match ret {
-        Ok(()) => log::info!("Command executed successfully"),
+    let success = ret.is_ok();
+    if let Err(e) = &ret {
+        let record = FaultRecord::new(command, e);
+        if let Err(e) = exec.lock().unwrap().fault_log.push(record) {
+            log::error!("Could not write fault log: {e}");
+        }
+    }
 
+    match ret {
+        Ok(()) => {
+            log::info!("Command executed successfully");
+            exec.lock().unwrap().recovery.reset();
+        }
         Err(CommandError::NonRecoverable(e)) => {
             log::error!("Non-Recoverable error: {e}");
-            panic!("Aborting now {e:?}");
+            recovery::handle_fault(&e, com, exec);
         }
         Err(CommandError::ProtocolViolation(e)) => {
             log::error!("Protocol Violation: {e}");
@@ -42,16 +146,22 @@ pub fn handle_command(com: &mut impl CommunicationHandle, exec: &mut SyncExecuti
         Err(CommandError::External(e)) => {
             log::error!("External error: {e}");
         }
-    };
+    }
+
+    if let Some(statistics) = com.statistics() {
+        if let Err(e) = statistics.lock().unwrap().save_if_due() {
+            log::error!("Could not persist communication statistics: {e}");
+        }
+    }
+
+    success
 }
 
-pub fn process_command(
-    com: &mut impl CommunicationHandle,
-    exec: &mut SyncExecutionContext,
-) -> CommandResult {
+/// Waits for the data packet that starts a command and returns its content, which is never empty
+pub fn receive_command(com: &mut impl CommunicationHandle) -> Result<Vec<u8>, CommandError> {
     let packet = com.receive_packet()?;
     let CEPPacket::Data(data) = packet else {
-        return Err(CommandError::NonRecoverable(anyhow!(
+        return Err(CommandError::ProtocolViolation(anyhow!(
             "Received {packet:?} as command start, expected Data"
         )));
     };
@@ -60,17 +170,22 @@ pub fn process_command(
         return Err(CommandError::ProtocolViolation(anyhow!("Received empty data packet")));
     }
 
-    match data.first().unwrap() {
-        0x01 => store_archive(&data, com, exec)?,
-        0x02 => execute_program(&data, com, exec)?,
-        0x03 => stop_program(&data, com, exec)?,
-        0x04 => get_status(&data, com, exec)?,
-        0x05 => return_result(&data, com, exec)?,
-        0x06 => update_time(&data, com, exec)?,
-        b => {
-            return Err(CommandError::ProtocolViolation(anyhow!("Unknown command {b:#x}")));
-        }
-    };
+    Ok(data)
+}
 
-    Ok(())
+/// Executes the command contained in `data`, which must not be empty
+pub fn process_command(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    dispatch(data, com, exec).unwrap_or_else(|| {
+        let error = CommandError::ProtocolViolation(anyhow!("Unknown command {:#x}", data[0]));
+        // A COBC that does not know about NACK reasons is not answered, as it never was
+        if com.capabilities().contains(Capabilities::NACK_REASON) {
+            send_nack(com, NackReason::UnknownCommand, error)
+        } else {
+            Err(error)
+        }
+    })
 }
@@ -0,0 +1,22 @@
+use super::{check_length, CommandResult, SyncExecutionContext};
+use crate::communication::{CEPPacket, Capabilities, CommunicationHandle};
+
+/// Handles the capability handshake. The COBC sends a bitfield of the optional features it
+/// supports and the EDU answers with the subset it supports as well. This subset is then used for
+/// the rest of the session, see [`Capabilities`] for when it ends.
+pub fn negotiate_capabilities(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    _exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 2)?;
+    // a rebooted COBC negotiates again, it can not decode what the previous session agreed on
+    com.set_capabilities(Capabilities::NONE);
+
+    let negotiated = Capabilities(data[1]) & Capabilities::SUPPORTED;
+    log::info!("Negotiated capabilities {:#04x}", negotiated.0);
+
+    com.send_packet(&CEPPacket::Data(vec![negotiated.0]))?;
+    com.set_capabilities(negotiated);
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use super::{check_length, pause_student_program, send_nack, CommandResult, SyncExecutionContext};
+use crate::communication::{CEPPacket, CommunicationHandle};
+
+/// Suspends the running student program until it is resumed. The time it is paused does not count
+/// towards its timeout.
+pub fn pause_program(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    if let Err((reason, e)) = pause_student_program(exec, true) {
+        return send_nack(com, reason, e);
+    }
+
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use crate::config::Configuration;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The environment variable that holds the directory of the store for a running student program
+pub const STORE_ENV: &str = "EDU_STORE";
+const MAXIMUM_KEY_LENGTH: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Store takes {size} bytes, only {quota} are allowed")]
+    QuotaExceeded { size: u64, quota: u64 },
+    #[error("Store has {count} keys, only {maximum} are allowed")]
+    TooManyKeys { count: usize, maximum: usize },
+    #[error("{0:?} is not a valid key")]
+    InvalidKey(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A persistent key-value store of a single student program, kept in `{store_path}/{program_id}`.
+/// Every key is a file in that directory, which contains the value.
+///
+/// A running program works on a copy of the store. The copy is committed once the program finished,
+/// unless it breaks the quota, in which case all changes of that run are discarded. A program whose
+/// copy grows beyond the quota is stopped, see `check_work_size`.
+pub struct ProgramStore {
+    path: PathBuf,
+    work: PathBuf,
+    quota: u64,
+    maximum_keys: usize,
+}
+
+impl ProgramStore {
+    #[must_use]
+    pub fn new(config: &Configuration, program_id: u16) -> Self {
+        Self {
+            path: config.store_path.join(program_id.to_string()),
+            work: config.store_path.join(format!("{program_id}.work")),
+            quota: config.store_quota,
+            maximum_keys: config.store_max_keys,
+        }
+    }
+
+    /// Prepares the copy a student program works on and returns its absolute path
+    pub fn checkout(&self) -> io::Result<PathBuf> {
+        remove_dir_if_exists(&self.work)?;
+        std::fs::create_dir_all(&self.work)?;
+        for (key, value) in self.entries()? {
+            std::fs::write(self.work.join(key), value)?;
+        }
+        std::fs::canonicalize(&self.work)
+    }
+
+    /// Replaces the store with the copy a student program worked on, if the copy is within the
+    /// quota. The copy is removed in any case.
+    pub fn commit(&self) -> Result<(), StoreError> {
+        let checked = check_quota(&self.work, self.quota, self.maximum_keys);
+        if checked.is_ok() {
+            let old = self.path.with_extension("old");
+            remove_dir_if_exists(&old)?;
+            if self.path.exists() {
+                std::fs::rename(&self.path, &old)?;
+            }
+            std::fs::rename(&self.work, &self.path)?;
+            remove_dir_if_exists(&old)?;
+        } else {
+            remove_dir_if_exists(&self.work)?;
+        }
+        checked
+    }
+
+    /// Returns an error if the copy a student program works on takes more space than the quota.
+    /// Unlike `commit`, this counts everything below the copy, including invalid keys.
+    pub fn check_work_size(&self) -> Result<(), StoreError> {
+        let size = match dir_size(&self.work) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0, // cleared while running
+            size => size?,
+        };
+        if size > self.quota {
+            return Err(StoreError::QuotaExceeded { size, quota: self.quota });
+        }
+        Ok(())
+    }
+
+    /// Removes all entries. If the program is running, the changes it makes are discarded as well.
+    pub fn clear(&self) -> io::Result<()> {
+        remove_dir_if_exists(&self.work)?;
+        remove_dir_if_exists(&self.path)
+    }
+
+    /// All entries, sorted by key
+    pub fn entries(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let dir = match std::fs::read_dir(&self.path) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for entry in dir {
+            let entry = entry?;
+            let Ok(key) = entry.file_name().into_string() else { continue };
+            entries.push((key, std::fs::read(entry.path())?));
+        }
+        entries.sort_unstable();
+        Ok(entries)
+    }
+
+    /// Encodes all entries as the length of the key (u8), the key, the length of the value (u32)
+    /// and the value
+    pub fn serialize(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for (key, value) in self.entries()? {
+            bytes.push(u8::try_from(key.len()).unwrap_or(u8::MAX));
+            bytes.extend(key.as_bytes());
+            bytes.extend(u32::try_from(value.len()).unwrap_or(u32::MAX).to_le_bytes());
+            bytes.extend(value);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Keys consist of up to `MAXIMUM_KEY_LENGTH` letters, digits, '-', '_' and '.', but must not
+/// start with a '.'
+fn is_valid_key(key: &str) -> bool {
+    (1..=MAXIMUM_KEY_LENGTH).contains(&key.len())
+        && !key.starts_with('.')
+        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+}
+
+fn check_quota(dir: &Path, quota: u64, maximum_keys: usize) -> Result<(), StoreError> {
+    let mut size = 0;
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let key = entry.file_name().to_string_lossy().into_owned();
+        let metadata = entry.path().symlink_metadata()?;
+        if !metadata.is_file() || !is_valid_key(&key) {
+            return Err(StoreError::InvalidKey(key));
+        }
+        size += metadata.len();
+        count += 1;
+    }
+
+    if size > quota {
+        return Err(StoreError::QuotaExceeded { size, quota });
+    }
+    if count > maximum_keys {
+        return Err(StoreError::TooManyKeys { count, maximum: maximum_keys });
+    }
+    Ok(())
+}
+
+/// Brings the stores back into a consistent state after the scheduler was interrupted. Must only be
+/// called while no student program is running.
+/// * a store that was interrupted while being committed is restored from `{program_id}.old`, or
+///   the leftover `.old` is removed if the commit already replaced the store
+/// * work directories, which were never committed, are removed
+pub fn recover_stores(config: &Configuration) -> io::Result<()> {
+    let stores = match std::fs::read_dir(&config.store_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        stores => stores?,
+    };
+
+    for entry in stores {
+        let path = entry?.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("old") if path.with_extension("").exists() => {
+                log::warn!("Removing {path:?} left over from a commit");
+                std::fs::remove_dir_all(path)?;
+            }
+            Some("old") => {
+                log::warn!("Restoring {path:?}, its commit was interrupted");
+                std::fs::rename(&path, path.with_extension(""))?;
+            }
+            Some("work") => {
+                log::warn!("Removing {path:?}, it was never committed");
+                std::fs::remove_dir_all(path)?;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// The size of all files below `path`, symlinks are not followed
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(size)
+}
+
+fn remove_dir_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(name: &str) -> ProgramStore {
+        let dir = PathBuf::from(format!("__store_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = Configuration {
+            store_path: dir,
+            store_quota: 10,
+            store_max_keys: 2,
+            ..Default::default()
+        };
+        ProgramStore::new(&config, 1)
+    }
+
+    #[test]
+    fn changes_are_committed_within_quota() {
+        let store = prepare("commit");
+
+        let work = store.checkout().unwrap();
+        std::fs::write(work.join("a"), b"1234").unwrap();
+        std::fs::write(work.join("b"), b"5678").unwrap();
+        store.commit().unwrap();
+
+        let work = store.checkout().unwrap();
+        assert_eq!(std::fs::read(work.join("a")).unwrap(), b"1234");
+        std::fs::write(work.join("a"), b"12345678").unwrap();
+        assert!(matches!(store.commit(), Err(StoreError::QuotaExceeded { size: 12, quota: 10 })));
+
+        let work = store.checkout().unwrap();
+        std::fs::write(work.join("c"), b"").unwrap();
+        assert!(matches!(store.commit(), Err(StoreError::TooManyKeys { count: 3, .. })));
+
+        let work = store.checkout().unwrap();
+        std::fs::create_dir(work.join("d")).unwrap();
+        assert!(matches!(store.commit(), Err(StoreError::InvalidKey(_))));
+
+        let expected = vec![("a".into(), b"1234".to_vec()), ("b".into(), b"5678".to_vec())];
+        assert_eq!(store.entries().unwrap(), expected);
+        assert!(!work.exists());
+
+        store.clear().unwrap();
+        assert!(store.entries().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all("__store_commit");
+    }
+
+    #[test]
+    fn work_size_includes_invalid_keys() {
+        let store = prepare("size");
+
+        let work = store.checkout().unwrap();
+        store.check_work_size().unwrap();
+        std::fs::create_dir(work.join("d")).unwrap();
+        std::fs::write(work.join("d").join("e"), b"12345678901").unwrap();
+        assert!(matches!(
+            store.check_work_size(),
+            Err(StoreError::QuotaExceeded { size: 11, quota: 10 })
+        ));
+
+        let _ = std::fs::remove_dir_all("__store_size");
+    }
+
+    #[test]
+    fn interrupted_commits_are_recovered() {
+        let dir = PathBuf::from("__store_recover");
+        let _ = std::fs::remove_dir_all(&dir);
+        for store in ["1.old", "2", "2.old", "3.work"] {
+            std::fs::create_dir_all(dir.join(store)).unwrap();
+            std::fs::write(dir.join(store).join("key"), store).unwrap();
+        }
+
+        let config = Configuration { store_path: dir.clone(), ..Default::default() };
+        recover_stores(&config).unwrap();
+
+        let mut stores: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        stores.sort_unstable();
+        assert_eq!(stores, ["1", "2"]);
+        assert_eq!(std::fs::read(dir.join("1/key")).unwrap(), b"1.old");
+        assert_eq!(std::fs::read(dir.join("2/key")).unwrap(), b"2");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keys_are_restricted() {
+        assert!(is_valid_key("counter_1.txt"));
+        assert!(!is_valid_key(""));
+        assert!(!is_valid_key(".hidden"));
+        assert!(!is_valid_key("a b"));
+        assert!(!is_valid_key(&"a".repeat(MAXIMUM_KEY_LENGTH + 1)));
+    }
+}
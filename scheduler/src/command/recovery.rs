@@ -0,0 +1,334 @@
+use super::{kill_student_group, Event, ExecutionContext, ResultId, SyncExecutionContext};
+use crate::{
+    communication::{CommunicationError, CommunicationHandle},
+    config::Configuration,
+};
+use std::io::ErrorKind;
+
+/// After this many consecutive faults, the scheduler gives up on local recovery and restarts
+const MAX_CONSECUTIVE_FAULTS: u32 = 3;
+
+/// Errors concerning the thread that supervises a student program
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorError {
+    #[error("Supervisor thread panicked")]
+    Panicked,
+    #[error("Supervisor thread did not finish in time")]
+    Timeout,
+}
+
+/// Classification of errors that could not be handled by a command itself. The discriminants are
+/// stored in the fault log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Fault {
+    /// The file system is full
+    StorageFull = 1,
+    /// A file or directory the scheduler relies on is missing
+    MissingPath = 2,
+    /// Reading from or writing to the COBC failed
+    Communication = 3,
+    /// The student program or its supervisor thread misbehaved
+    Supervisor = 4,
+    /// Anything else, there is no local recovery for it
+    Unknown = 5,
+}
+
+impl Fault {
+    #[must_use]
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(CommunicationError::Io(_)) = cause.downcast_ref::<CommunicationError>() {
+                return Fault::Communication;
+            }
+            if cause.is::<SupervisorError>() || cause.is::<subprocess::PopenError>() {
+                return Fault::Supervisor;
+            }
+            if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                return match e.kind() {
+                    ErrorKind::StorageFull => Fault::StorageFull,
+                    ErrorKind::NotFound => Fault::MissingPath,
+                    _ => Fault::Unknown,
+                };
+            }
+        }
+
+        Fault::Unknown
+    }
+
+    /// Tries to remove the cause of the fault. Returns false if a recovery action failed.
+    pub fn recover(self, com: &mut impl CommunicationHandle, exec: &SyncExecutionContext) -> bool {
+        let result = match self {
+            Fault::StorageFull => free_space(&mut exec.lock().unwrap()),
+            Fault::MissingPath => {
+                let config = &exec.lock().unwrap().config;
+                std::fs::create_dir_all(&config.archives_path)
+                    .and_then(|()| std::fs::create_dir_all(&config.data_path))
+            }
+            Fault::Communication => com.reset().map_err(std::io::Error::other),
+            Fault::Supervisor => abandon_supervisor(&mut exec.lock().unwrap()),
+            // There is nothing that could be done locally, rely on the consecutive fault limit
+            Fault::Unknown => Ok(()),
+        };
+
+        if let Err(e) = &result {
+            log::error!("Recovery from {self:?} failed: {e}");
+        }
+        result.is_ok()
+    }
+}
+
+/// The supervisor can not be joined, so it is forgotten and new programs can be started. Its student
+/// program is killed first, as it would otherwise keep running next to the new one.
+fn abandon_supervisor(exec: &mut ExecutionContext) -> std::io::Result<()> {
+    if let Some(pid) = exec.student_pid {
+        kill_student_group(pid, exec.paused, exec.config.terminate_timeout())?;
+    }
+
+    exec.thread_handle = None;
+    exec.run += 1; // the abandoned supervisor must not clear the state of the next run
+    exec.running_flag = false;
+    exec.api_session = None;
+    exec.student_pid = None;
+    exec.paused = false;
+    Ok(())
+}
+
+/// Removes files that are not needed anymore. If there are none, the oldest result that was not
+/// returned yet is dropped. Fails if nothing could be removed.
+fn free_space(exec: &mut ExecutionContext) -> std::io::Result<()> {
+    let running = exec
+        .api_session
+        .as_ref()
+        .map(|s| ResultId { program_id: s.program_id, timestamp: s.timestamp });
+
+    if remove_leftovers(&exec.config, running)? || drop_oldest_result(exec)? {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(ErrorKind::StorageFull, "Found nothing to remove"))
+    }
+}
+
+/// Removes archives left over from failed uploads, logs of programs that are not running anymore
+/// and work directories of stores that were never committed. Returns wether anything was removed.
+fn remove_leftovers(config: &Configuration, running: Option<ResultId>) -> std::io::Result<bool> {
+    let running_log = running.map(|r| format!("{r}.log"));
+    let running_work = running.map(|r| format!("{}.work", r.program_id));
+    let mut removed = false;
+
+    for entry in std::fs::read_dir(&config.data_path)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str());
+        let is_leftover = match path.extension().and_then(|e| e.to_str()) {
+            Some("zip") => true,
+            Some("log") => name != running_log.as_deref(),
+            _ => false,
+        };
+        if is_leftover && path.is_file() {
+            log::warn!("Removing {path:?} to free up space");
+            std::fs::remove_file(path)?;
+            removed = true;
+        }
+    }
+
+    let stores = match std::fs::read_dir(&config.store_path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(removed),
+        stores => stores?,
+    };
+    for entry in stores {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str());
+        if path.extension().is_some_and(|e| e == "work") && name != running_work.as_deref() {
+            log::warn!("Removing {path:?} to free up space");
+            std::fs::remove_dir_all(path)?;
+            removed = true;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Removes the oldest result archive, along with the event announcing it to the COBC. Returns
+/// wether there was one.
+fn drop_oldest_result(exec: &mut ExecutionContext) -> std::io::Result<bool> {
+    let Some((index, result)) = exec.events.as_ref().iter().enumerate().find_map(|(i, e)| {
+        if let Event::Result(result) = e.event {
+            Some((i, result))
+        } else {
+            None
+        }
+    }) else {
+        return Ok(false);
+    };
+
+    log::warn!("Dropping result {result} to free up space");
+    // the archive is removed first, rewriting the event queue needs space itself
+    match std::fs::remove_file(exec.config.data_file(result)) {
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        removed => removed?,
+    }
+    exec.events.remove(index)?;
+    Ok(true)
+}
+
+/// Decides wether the scheduler can continue after a fault
+#[derive(Default)]
+pub struct RecoveryPolicy {
+    consecutive_faults: u32,
+}
+
+impl RecoveryPolicy {
+    /// Should be called after every successfully executed command
+    pub fn reset(&mut self) {
+        self.consecutive_faults = 0;
+    }
+
+    /// Counts the fault and returns wether the scheduler should be restarted
+    pub fn register(&mut self, recovered: bool) -> bool {
+        self.consecutive_faults += 1;
+        !recovered || self.consecutive_faults >= MAX_CONSECUTIVE_FAULTS
+    }
+}
+
+/// Tries to recover from a fault that a command could not handle. Only if that fails, or the
+/// faults keep repeating, the scheduler panics so that it is restarted.
+pub fn handle_fault(
+    error: &anyhow::Error,
+    com: &mut impl CommunicationHandle,
+    exec: &SyncExecutionContext,
+) {
+    let fault = Fault::classify(error);
+    log::warn!("Trying to recover from {fault:?}");
+    let recovered = fault.recover(com, exec);
+
+    let restart = exec.lock().unwrap().recovery.register(recovered);
+    if restart {
+        log::error!("Could not recover from {fault:?}, restarting");
+        if let Some(statistics) = com.statistics() {
+            if let Err(e) = statistics.lock().unwrap().save() {
+                log::error!("Could not persist communication statistics: {e}");
+            }
+        }
+        panic!("Aborting now {error:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::{
+            group_is_alive, start_program, terminate_student_program, MemoryNotifier, RetryEvent,
+        },
+        student_api::ApiSession,
+    };
+    use std::{os::unix::process::CommandExt, path::Path};
+
+    fn context(dir: &str) -> SyncExecutionContext {
+        let _ = std::fs::remove_dir_all(dir);
+        let dir = Path::new(dir);
+        let config = Configuration {
+            archives_path: dir.join("archives"),
+            data_path: dir.join("data"),
+            store_path: dir.join("store"),
+            events_path: dir.join("events"),
+            fault_log_path: dir.join("faults"),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&config.data_path).unwrap();
+        std::fs::create_dir_all(&config.store_path).unwrap();
+        ExecutionContext::with_notifier(config, Box::new(MemoryNotifier::default())).unwrap()
+    }
+
+    #[test]
+    fn space_is_freed_without_touching_the_running_program() {
+        let dir = "__recovery_free_space";
+        let exec = context(dir);
+        let mut l_exec = exec.lock().unwrap();
+        l_exec.api_session = Some(ApiSession::new(1, 2).unwrap());
+        let result = ResultId { program_id: 7, timestamp: 8 };
+        l_exec.events.push(RetryEvent::new(Event::Result(result), 5)).unwrap();
+
+        let data = Path::new(dir).join("data");
+        let store = Path::new(dir).join("store");
+        for file in ["3.zip", "1_2.log", "4_5.log", "7_8"] {
+            std::fs::write(data.join(file), [0; 16]).unwrap();
+        }
+        for store_dir in ["1.work", "3.work", "3"] {
+            std::fs::create_dir(store.join(store_dir)).unwrap();
+        }
+
+        free_space(&mut l_exec).unwrap();
+        assert!(!data.join("3.zip").exists());
+        assert!(!data.join("4_5.log").exists());
+        assert!(!store.join("3.work").exists());
+        assert!(data.join("1_2.log").exists());
+        assert!(data.join("7_8").exists());
+        assert!(store.join("1.work").exists());
+        assert!(store.join("3").exists());
+
+        free_space(&mut l_exec).unwrap();
+        assert!(!data.join("7_8").exists());
+        assert!(l_exec.events.is_empty());
+
+        assert_eq!(free_space(&mut l_exec).unwrap_err().kind(), ErrorKind::StorageFull);
+
+        drop(l_exec);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn abandoned_program_is_killed() {
+        let dir = "__recovery_abandon_supervisor";
+        let exec = context(dir);
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 60 & sleep 60"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        // SAFETY: killpg only sends a signal and has no memory safety requirements
+        assert_eq!(unsafe { libc::killpg(child.id().try_into().unwrap(), libc::SIGSTOP) }, 0);
+
+        let mut l_exec = exec.lock().unwrap();
+        l_exec.student_pid = Some(child.id());
+        l_exec.paused = true;
+        l_exec.running_flag = true;
+        abandon_supervisor(&mut l_exec).unwrap();
+
+        assert!(!group_is_alive(child.id()));
+        assert!(!l_exec.running_flag);
+        assert_eq!(l_exec.student_pid, None);
+        child.wait().unwrap();
+
+        drop(l_exec);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn abandoned_supervisor_leaves_next_run_alone() {
+        let dir = "__recovery_abandon_next_run";
+        let mut exec = context(dir);
+        let program = exec.lock().unwrap().config.program_path(1);
+        std::fs::create_dir_all(&program).unwrap();
+        std::fs::write(program.join("main.py"), "import time\ntime.sleep(60)\n").unwrap();
+        let timeout = std::time::Duration::from_secs(30);
+
+        start_program(&mut exec, 1, 1, timeout).unwrap();
+        abandon_supervisor(&mut exec.lock().unwrap()).unwrap();
+        start_program(&mut exec, 1, 2, timeout).unwrap();
+        // the abandoned supervisor notices that its program was killed in the meantime
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let l_exec = exec.lock().unwrap();
+        assert!(l_exec.running_flag);
+        assert_eq!(l_exec.api_session.as_ref().map(|s| s.timestamp), Some(2));
+        let pid = l_exec.student_pid.unwrap();
+        assert!(group_is_alive(pid));
+        drop(l_exec);
+
+        terminate_student_program(&mut exec).unwrap();
+        assert!(!group_is_alive(pid));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
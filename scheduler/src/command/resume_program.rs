@@ -0,0 +1,18 @@
+use super::{check_length, pause_student_program, send_nack, CommandResult, SyncExecutionContext};
+use crate::communication::{CEPPacket, CommunicationHandle};
+
+/// Continues the student program that was suspended by Pause Program
+pub fn resume_program(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 1)?;
+
+    if let Err((reason, e)) = pause_student_program(exec, false) {
+        return send_nack(com, reason, e);
+    }
+
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
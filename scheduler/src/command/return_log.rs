@@ -0,0 +1,35 @@
+use super::{
+    check_length, send_nack, CommandError, CommandResult, NackReason, SyncExecutionContext,
+};
+use crate::{communication::CommunicationHandle, logging};
+use anyhow::anyhow;
+
+/// Sends the scheduler log with the index given in the second byte. Index 0 is the current log,
+/// 1 to `log_files` are the rotated ones, with higher indices being older.
+pub fn return_log(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length(com, data, 2)?;
+
+    let index = data[1];
+    let config = exec.lock().unwrap().config.clone();
+    let path = logging::rotated_log_path(&config.log_path, index);
+    if index > config.log_files || !path.exists() {
+        return send_nack(
+            com,
+            NackReason::LogNotFound,
+            CommandError::ProtocolViolation(anyhow!("Log {index} does not exist")),
+        );
+    }
+
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return send_nack(com, NackReason::Storage, e.into()),
+    };
+    log::info!("Returning log {index}");
+    com.send_multi_packet(&bytes)?;
+
+    Ok(())
+}
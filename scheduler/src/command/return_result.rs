@@ -1,47 +1,59 @@
 use super::{CommandResult, SyncExecutionContext};
 use crate::{
-    command::{check_length, CommandError, Event, ResultId, COMMAND_TIMEOUT},
-    communication::{CEPPacket, CommunicationHandle},
+    command::{check_length_between, send_nack, CommandError, Event, NackReason, ResultId},
+    communication::CommunicationHandle,
 };
 use anyhow::anyhow;
 
 /// Handles a complete return result command. The result tar file is only deleted if a final Ack is
-/// received.
+/// received. An optional eighth byte requests a windowed transfer with the given window size,
+/// otherwise every packet is acknowledged before the next one is sent.
 pub fn return_result(
     data: &[u8],
     com: &mut impl CommunicationHandle,
     exec: &mut SyncExecutionContext,
 ) -> CommandResult {
-    check_length(com, data, 7)?;
+    check_length_between(com, data, 7, 8)?;
 
     let program_id = u16::from_le_bytes([data[1], data[2]]);
     let timestamp = u32::from_le_bytes([data[3], data[4], data[5], data[6]]);
-    let result_path = format!("./data/{program_id}_{timestamp}");
+    let window = data.get(7).copied().unwrap_or(1);
+    let result_id = ResultId { program_id, timestamp };
+    let config = exec.lock().unwrap().config.clone();
+    let result_path = config.data_file(result_id);
 
-    if !std::path::Path::new(&result_path).exists() {
-        com.send_packet(&CEPPacket::Nack)?;
-        return Err(CommandError::ProtocolViolation(anyhow!(
-            "Result {program_id}:{timestamp} does not exist"
-        )));
+    if !result_path.exists() {
+        return send_nack(
+            com,
+            NackReason::ResultNotFound,
+            CommandError::ProtocolViolation(anyhow!(
+                "Result {program_id}:{timestamp} does not exist"
+            )),
+        );
     }
 
-    let bytes = std::fs::read(result_path)?;
+    let bytes = match std::fs::read(&result_path) {
+        Ok(bytes) => bytes,
+        Err(e) => return send_nack(com, NackReason::Storage, e.into()),
+    };
     log::info!("Returning result for {}:{}", program_id, timestamp);
-    com.send_multi_packet(&bytes)?;
+    if window > 1 {
+        com.send_multi_packet_windowed(&bytes, window.into())?;
+    } else {
+        com.send_multi_packet(&bytes)?;
+    }
 
-    com.await_ack(COMMAND_TIMEOUT)?;
-    let result_id = ResultId { program_id, timestamp };
-    let _ = std::fs::remove_file(format!("./data/{result_id}"));
+    com.await_ack(config.command_timeout())?;
+    let _ = std::fs::remove_file(result_path);
 
     let mut l_exec = exec.lock().unwrap();
     if let Some(event_index) =
-        l_exec.event_vec.as_ref().iter().position(|x| x.event == Event::Result(result_id))
+        l_exec.events.as_ref().iter().position(|x| x.event == Event::Result(result_id))
     {
-        l_exec.event_vec.remove(event_index)?;
+        l_exec.events.remove(event_index)?;
     } else {
         log::error!("Could not find event entry for existing result file {program_id}:{timestamp}");
     }
 
-    l_exec.configure_update_pin();
     Ok(())
 }
@@ -0,0 +1,62 @@
+use super::{
+    check_length_between, send_nack, CommandError, CommandResult, NackReason, SyncExecutionContext,
+};
+use crate::{
+    communication::{CEPPacket, CommunicationHandle},
+    config::ConfigError,
+    logging,
+};
+use anyhow::anyhow;
+
+const MAXIMUM_ASSIGNMENT_LENGTH: usize = 1024;
+
+/// Changes a single configuration key. The data following the command id is a TOML assignment,
+/// e.g. `heartbeat_freq = 20`. The change is persisted and answered with a data packet containing
+/// 0 if it was applied immediately, or 1 if it only takes effect after a restart.
+pub fn set_config(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length_between(com, data, 2, 1 + MAXIMUM_ASSIGNMENT_LENGTH)?;
+    let assignment = String::from_utf8_lossy(&data[1..]);
+
+    let mut l_exec = exec.lock().unwrap();
+    let previous_filter = l_exec.config.log_filter();
+    let result = l_exec.config.set(&assignment);
+    let current_filter = l_exec.config.log_filter();
+    drop(l_exec);
+
+    let restart = match result {
+        Ok(restart) => restart,
+        Err(errors) => {
+            for e in &errors {
+                log::warn!("Rejected configuration change {assignment:?}: {e}");
+            }
+            let reason = if errors.iter().any(|e| matches!(e, ConfigError::Io(_))) {
+                NackReason::Storage
+            } else {
+                NackReason::InvalidParameter
+            };
+            return send_nack(
+                com,
+                reason,
+                CommandError::ProtocolViolation(anyhow!("Invalid configuration change")),
+            );
+        }
+    };
+
+    if let (Ok(previous), Ok(current)) = (previous_filter, current_filter) {
+        if previous != current {
+            logging::set_filter(current);
+        }
+    }
+
+    if restart {
+        log::warn!("Configuration changed: {assignment}, it only takes effect after a restart");
+    } else {
+        log::info!("Configuration changed: {assignment}");
+    }
+    com.send_packet(&CEPPacket::Data(vec![u8::from(restart)]))?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use super::{
+    check_length_between, send_nack, CommandError, CommandResult, NackReason, SyncExecutionContext,
+};
+use crate::{
+    communication::{CEPPacket, CommunicationHandle},
+    logging,
+};
+use anyhow::anyhow;
+use log::LevelFilter;
+
+const MAXIMUM_MODULE_LENGTH: usize = 64;
+
+/// Changes the log level until the scheduler is restarted. The second byte is the level, from 0
+/// (off) to 5 (trace). It is followed by an optional module name, e.g. `communication`. Without
+/// one, the default level is changed.
+pub fn set_log_level(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    _exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    check_length_between(com, data, 2, 2 + MAXIMUM_MODULE_LENGTH)?;
+
+    let level = LevelFilter::iter().nth(data[1].into());
+    let module = std::str::from_utf8(&data[2..]);
+    let (Some(level), Ok(module)) = (level, module) else {
+        return send_nack(
+            com,
+            NackReason::InvalidParameter,
+            CommandError::ProtocolViolation(anyhow!("Invalid log level or module name")),
+        );
+    };
+
+    let directive = if module.is_empty() { level.to_string() } else { format!("{module}={level}") };
+    let filter = match logging::apply_directives(&directive) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return send_nack(
+                com,
+                NackReason::InvalidParameter,
+                CommandError::ProtocolViolation(anyhow!(
+                    "Invalid log directive {directive:?}: {e}"
+                )),
+            );
+        }
+    };
+    log::info!("Log filter changed to {filter}");
+
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
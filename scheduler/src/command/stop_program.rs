@@ -1,4 +1,6 @@
-use super::{check_length, terminate_student_program, CommandResult, SyncExecutionContext};
+use super::{
+    check_length, send_nack, terminate_student_program, CommandResult, SyncExecutionContext,
+};
 use crate::communication::{CEPPacket, CommunicationHandle};
 
 /// Stops the currently running student program
@@ -9,7 +11,9 @@ pub fn stop_program(
 ) -> CommandResult {
     check_length(com, data, 1)?;
 
-    terminate_student_program(exec).expect("to terminate student program");
+    if let Err((reason, e)) = terminate_student_program(exec) {
+        return send_nack(com, reason, e);
+    }
 
     com.send_packet(&CEPPacket::Ack)?;
     Ok(())
@@ -1,24 +1,44 @@
 use super::{CommandError, CommandResult, SyncExecutionContext};
 use crate::{
-    command::check_length,
+    command::{check_length, common::verify_signature, send_nack, NackReason},
     communication::{CEPPacket, CommunicationHandle},
+    config::Configuration,
 };
 use anyhow::anyhow;
+use ed25519_dalek::SIGNATURE_LENGTH;
 use std::{io::Write, process::Command};
 
-/// This function implements the Store Archive command, including the reception of the archive itself
+/// This function implements the Store Archive command, including the reception of the archive itself.
+/// The program id may be followed by an Ed25519 signature of the program id (u16, little endian)
+/// followed by the archive, which is required if trusted keys are configured. Signing the id as
+/// well prevents a signed archive from being stored as another program.
 pub fn store_archive(
     data: &[u8],
     com: &mut impl CommunicationHandle,
-    _exec: &mut SyncExecutionContext,
+    exec: &mut SyncExecutionContext,
 ) -> CommandResult {
-    check_length(com, data, 3)?;
+    if data.len() != 3 {
+        check_length(com, data, 3 + SIGNATURE_LENGTH)?;
+    }
 
-    let id = u16::from_le_bytes([data[1], data[2]]).to_string();
+    let id = u16::from_le_bytes([data[1], data[2]]);
     log::info!("Storing Archive {}", id);
 
     let bytes = com.receive_multi_packet()?;
-    unpack_archive(&id, &bytes)?;
+    let config = exec.lock().unwrap().config.clone();
+    let keys = config.trusted_keys();
+    if !keys.is_empty() {
+        let message = [&data[1..3], &bytes].concat();
+        if let Err(e) = verify_signature(&keys, &message, &data[3..]) {
+            return send_nack(com, NackReason::InvalidSignature, CommandError::External(e));
+        }
+    }
+
+    if let Err(e) = unpack_archive(&config, id, &bytes) {
+        // Only errors of the file system carry an OS error code, a failed unzip does not
+        let reason = if e.detail() == 0 { NackReason::InvalidArchive } else { NackReason::Storage };
+        return send_nack(com, reason, e);
+    }
 
     com.send_packet(&CEPPacket::Ack)?;
     Ok(())
@@ -26,13 +46,13 @@ pub fn store_archive(
 
 /// Stores a received program in the appropriate folder and unzips it
 ///
-/// * `folder` The folder to unzip into, subsequently the program id
+/// * `program_id` The id of the program, which determines the folder to unzip into
 /// * `bytes` A vector containing the raw bytes of the zip archive
 ///
 /// Returns Ok or passes along a file access/unzip process error
-fn unpack_archive(folder: &str, bytes: &[u8]) -> CommandResult {
+fn unpack_archive(config: &Configuration, program_id: u16, bytes: &[u8]) -> CommandResult {
     // Store bytes into temporary file
-    let zip_path = format!("./data/{folder}.zip");
+    let zip_path = config.data_file(format!("{program_id}.zip"));
     let mut zip_file = std::fs::File::create(&zip_path)?;
     zip_file.write_all(bytes)?;
     zip_file.sync_all()?;
@@ -41,7 +61,7 @@ fn unpack_archive(folder: &str, bytes: &[u8]) -> CommandResult {
         .arg("-o") // overwrite silently
         .arg(&zip_path)
         .arg("-d") // target directory
-        .arg(format!("./archives/{folder}"))
+        .arg(config.program_path(program_id))
         .status();
 
     // Remove the temporary file, even if unzip failed
@@ -50,7 +70,8 @@ fn unpack_archive(folder: &str, bytes: &[u8]) -> CommandResult {
     match exit_status {
         Ok(status) => {
             if !status.success() {
-                return Err(CommandError::NonRecoverable(anyhow!("unzip failed")));
+                // a corrupted archive is the fault of the sender, not of the scheduler
+                return Err(CommandError::External(anyhow!("unzip failed")));
             }
         }
         Err(err) => {
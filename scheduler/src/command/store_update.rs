@@ -0,0 +1,47 @@
+use super::{
+    check_length, common::verify_signature, send_nack, CommandError, CommandResult, NackReason,
+    SyncExecutionContext,
+};
+use crate::{
+    communication::{CEPPacket, CommunicationHandle},
+    update::{UpdateError, Updater, CHECKSUM_LENGTH},
+};
+use ed25519_dalek::SIGNATURE_LENGTH;
+
+/// Receives a new scheduler binary and stages it, if it matches the SHA-256 checksum that follows
+/// the command id. The checksum may be followed by an Ed25519 signature of the binary, which is
+/// required if trusted keys are configured. The binary is only installed by the Activate Update
+/// command.
+pub fn store_update(
+    data: &[u8],
+    com: &mut impl CommunicationHandle,
+    exec: &mut SyncExecutionContext,
+) -> CommandResult {
+    if data.len() != 1 + CHECKSUM_LENGTH {
+        check_length(com, data, 1 + CHECKSUM_LENGTH + SIGNATURE_LENGTH)?;
+    }
+    log::info!("Receiving scheduler update");
+
+    let binary = com.receive_multi_packet()?;
+    let config = exec.lock().unwrap().config.clone();
+    let keys = config.trusted_keys();
+    if !keys.is_empty() {
+        if let Err(e) = verify_signature(&keys, &binary, &data[1 + CHECKSUM_LENGTH..]) {
+            return send_nack(com, NackReason::InvalidSignature, CommandError::External(e));
+        }
+    }
+
+    let updater = Updater::for_current_executable(&config)?;
+    match updater.stage(&binary, &data[1..=CHECKSUM_LENGTH]) {
+        Ok(()) => (),
+        Err(e @ UpdateError::ChecksumMismatch { .. }) => {
+            return send_nack(com, NackReason::InvalidChecksum, CommandError::External(e.into()));
+        }
+        Err(UpdateError::Io(e)) => return send_nack(com, NackReason::Storage, e.into()),
+        Err(e) => return Err(CommandError::NonRecoverable(e.into())),
+    }
+
+    log::info!("Staged scheduler update with {} bytes", binary.len());
+    com.send_packet(&CEPPacket::Ack)?;
+    Ok(())
+}
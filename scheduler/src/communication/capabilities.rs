@@ -0,0 +1,35 @@
+use std::ops::BitAnd;
+
+/// A set of optional protocol features, which the COBC and EDU agree on with the capability
+/// handshake. Serialized as a single bitfield byte.
+///
+/// The negotiated set only lasts for the session of the COBC, which may reboot while the EDU keeps
+/// running. It is reset to `NONE` whenever the COBC starts a valid handshake, as a COBC does at the
+/// start of every session. The answer to the handshake is always sent without any optional feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities(pub u8);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    /// Data packets may be sent deflate compressed. This does not apply to window data packets,
+    /// windowed transfers are always sent uncompressed.
+    pub const COMPRESSION: Self = Self(0x01);
+    /// A NACK in response to a command is followed by a data packet describing the failure
+    pub const NACK_REASON: Self = Self(0x02);
+
+    /// All capabilities supported by this scheduler
+    pub const SUPPORTED: Self = Self(Self::COMPRESSION.0 | Self::NACK_REASON.0);
+
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
@@ -1,5 +1,6 @@
 use crc::{Crc, CRC_32_MPEG_2};
-use std::io::Read;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use std::io::{Read, Write};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CEPPacket {
@@ -7,6 +8,12 @@ pub enum CEPPacket {
     Nack,
     Eof,
     Data(Vec<u8>),
+    /// A data packet carrying a sequence number, used by windowed multi packet transfers
+    WindowData(u16, Vec<u8>),
+    /// Selectively acknowledges the window data packet with the given sequence number
+    WindowAck(u16),
+    /// Selectively rejects the window data packet with the given sequence number
+    WindowNack(u16),
 }
 
 #[derive(Clone, Copy, strum::FromRepr)]
@@ -15,6 +22,11 @@ pub enum CEPPacketHeader {
     Nack = 0x27,
     Eof = 0x59,
     Data = 0x8b,
+    WindowData = 0x60,
+    WindowAck = 0xb1,
+    WindowNack = 0x12,
+    /// A data packet with a deflate compressed payload. Decoded into `CEPPacket::Data`
+    CompressedData = 0x7e,
 }
 
 impl CEPPacket {
@@ -23,16 +35,24 @@ impl CEPPacket {
 
     const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_MPEG_2);
 
-    /// Calculates the CRC32 MPEG-2 checksum for the contained data. For variants other than `Self::Data`, 0 is returned
+    /// Calculates the CRC32 MPEG-2 checksum for the contained data. For window data packets, the
+    /// sequence number is included. For all other variants, 0 is returned
     #[must_use]
     pub fn checksum(&self) -> u32 {
-        if let Self::Data(data) = self {
-            Self::CRC.checksum(data)
-        } else {
-            0
+        match self {
+            Self::Data(data) => Self::CRC.checksum(data),
+            Self::WindowData(seq, data) => Self::window_checksum(*seq, data),
+            _ => 0,
         }
     }
 
+    fn window_checksum(seq: u16, data: &[u8]) -> u32 {
+        let mut digest = Self::CRC.digest();
+        digest.update(&seq.to_le_bytes());
+        digest.update(data);
+        digest.finalize()
+    }
+
     #[must_use]
     pub fn serialize(&self) -> Vec<u8> {
         let header = self.header();
@@ -46,10 +66,47 @@ impl CEPPacket {
                 v.extend(crc32.to_le_bytes());
                 v
             }
+            CEPPacket::WindowData(seq, bytes) => {
+                let mut v = Vec::with_capacity(9 + bytes.len());
+                v.push(header);
+                v.extend(u16::try_from(bytes.len()).unwrap().to_le_bytes());
+                v.extend(seq.to_le_bytes());
+                v.extend(bytes);
+                v.extend(Self::window_checksum(*seq, bytes).to_le_bytes());
+                v
+            }
+            CEPPacket::WindowAck(seq) | CEPPacket::WindowNack(seq) => {
+                // The sequence number is repeated inverted, so that corruption can be detected
+                let mut v = vec![header];
+                v.extend(seq.to_le_bytes());
+                v.extend((!seq).to_le_bytes());
+                v
+            }
             _ => vec![header],
         }
     }
 
+    /// Like `serialize`, but a data packet is sent with a compressed payload if that makes it
+    /// smaller. Other variants, including window data packets, are serialized unchanged, see
+    /// `Capabilities::COMPRESSION`.
+    #[must_use]
+    pub fn serialize_compressed(&self) -> Vec<u8> {
+        let CEPPacket::Data(bytes) = self else {
+            return self.serialize();
+        };
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        let compressed = encoder.write_all(bytes).and_then(|()| encoder.finish());
+        match compressed {
+            Ok(compressed) if compressed.len() < bytes.len() => {
+                let mut v = CEPPacket::Data(compressed).serialize();
+                v[0] = CEPPacketHeader::CompressedData as u8;
+                v
+            }
+            _ => self.serialize(),
+        }
+    }
+
     #[must_use]
     pub fn crc_is_valid(data: &[u8], checksum: u32) -> bool {
         CEPPacket::CRC.checksum(data) == checksum
@@ -62,6 +119,9 @@ impl CEPPacket {
             CEPPacket::Nack => CEPPacketHeader::Nack,
             CEPPacket::Eof => CEPPacketHeader::Eof,
             CEPPacket::Data(_) => CEPPacketHeader::Data,
+            CEPPacket::WindowData(..) => CEPPacketHeader::WindowData,
+            CEPPacket::WindowAck(_) => CEPPacketHeader::WindowAck,
+            CEPPacket::WindowNack(_) => CEPPacketHeader::WindowNack,
         };
         header as u8
     }
@@ -76,14 +136,8 @@ impl CEPPacket {
             CEPPacketHeader::Ack => CEPPacket::Ack,
             CEPPacketHeader::Nack => CEPPacket::Nack,
             CEPPacketHeader::Eof => CEPPacket::Eof,
-            CEPPacketHeader::Data => {
-                let mut length_buffer = [0; 2];
-                reader.read_exact(&mut length_buffer)?;
-                let length = u16::from_le_bytes(length_buffer);
-
-                if length as usize > Self::MAXIMUM_DATA_LENGTH {
-                    return Err(CEPParseError::InvalidLength(length));
-                }
+            CEPPacketHeader::Data | CEPPacketHeader::CompressedData => {
+                let length = Self::read_length(reader)?;
 
                 let mut data_buffer = vec![0; length as usize];
                 reader.read_exact(&mut data_buffer)?;
@@ -94,12 +148,75 @@ impl CEPPacket {
                     return Err(CEPParseError::InvalidCRC);
                 }
 
+                if matches!(header, CEPPacketHeader::CompressedData) {
+                    data_buffer = Self::decompress(&data_buffer)?;
+                }
+
                 CEPPacket::Data(data_buffer)
             }
+            CEPPacketHeader::WindowData => {
+                let length = Self::read_length(reader)?;
+
+                let mut seq_buffer = [0; 2];
+                reader.read_exact(&mut seq_buffer)?;
+                let seq = u16::from_le_bytes(seq_buffer);
+
+                let mut data_buffer = vec![0; length as usize];
+                reader.read_exact(&mut data_buffer)?;
+
+                let mut crc_buffer = [0; 4];
+                reader.read_exact(&mut crc_buffer)?;
+                if Self::window_checksum(seq, &data_buffer) != u32::from_le_bytes(crc_buffer) {
+                    return Err(CEPParseError::InvalidWindowCRC(seq));
+                }
+
+                CEPPacket::WindowData(seq, data_buffer)
+            }
+            CEPPacketHeader::WindowAck | CEPPacketHeader::WindowNack => {
+                let mut seq_buffer = [0; 4];
+                reader.read_exact(&mut seq_buffer)?;
+                let seq = u16::from_le_bytes([seq_buffer[0], seq_buffer[1]]);
+                if !seq != u16::from_le_bytes([seq_buffer[2], seq_buffer[3]]) {
+                    return Err(CEPParseError::InvalidSequenceNumber);
+                }
+
+                if matches!(header, CEPPacketHeader::WindowAck) {
+                    CEPPacket::WindowAck(seq)
+                } else {
+                    CEPPacket::WindowNack(seq)
+                }
+            }
         };
 
         Ok(packet)
     }
+
+    /// Inflates a compressed payload, which must not exceed `MAXIMUM_DATA_LENGTH` once decoded
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, CEPParseError> {
+        let mut decompressed = Vec::new();
+        DeflateDecoder::new(data)
+            .take(Self::MAXIMUM_DATA_LENGTH as u64 + 1)
+            .read_to_end(&mut decompressed)
+            .map_err(|_| CEPParseError::InvalidCompression)?;
+
+        if decompressed.len() > Self::MAXIMUM_DATA_LENGTH {
+            return Err(CEPParseError::InvalidCompression);
+        }
+
+        Ok(decompressed)
+    }
+
+    fn read_length(reader: &mut (impl Read + ?Sized)) -> Result<u16, CEPParseError> {
+        let mut length_buffer = [0; 2];
+        reader.read_exact(&mut length_buffer)?;
+        let length = u16::from_le_bytes(length_buffer);
+
+        if length as usize > Self::MAXIMUM_DATA_LENGTH {
+            return Err(CEPParseError::InvalidLength(length));
+        }
+
+        Ok(length)
+    }
 }
 
 impl From<&CEPPacket> for Vec<u8> {
@@ -116,6 +233,12 @@ pub enum CEPParseError {
     InvalidHeader(u8),
     #[error("Invalid CRC checksum")]
     InvalidCRC,
+    #[error("Invalid CRC checksum for window data packet {0}")]
+    InvalidWindowCRC(u16),
+    #[error("Corrupted sequence number in window acknowledgement")]
+    InvalidSequenceNumber,
+    #[error("Compressed payload could not be decoded")]
+    InvalidCompression,
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
@@ -144,6 +267,52 @@ mod tests {
         assert_eq!(CEPPacket::try_from(vec).unwrap(), packet);
     }
 
+    #[allow(clippy::needless_pass_by_value)]
+    #[test_case(CEPPacket::WindowData(3, vec![1, 2, 3]))]
+    #[test_case(CEPPacket::WindowData(0xffff, vec![]); "empty window data packet")]
+    #[test_case(CEPPacket::WindowAck(0x1234))]
+    #[test_case(CEPPacket::WindowNack(7))]
+    fn window_packet_roundtrips(packet: CEPPacket) {
+        assert_eq!(CEPPacket::try_from(packet.serialize()).unwrap(), packet);
+    }
+
+    #[test]
+    fn window_data_crc_covers_sequence_number() {
+        let mut bytes = CEPPacket::WindowData(1, vec![1, 2, 3]).serialize();
+        bytes[3] = 2;
+        assert!(matches!(CEPPacket::try_from(bytes), Err(CEPParseError::InvalidWindowCRC(2))));
+    }
+
+    #[test]
+    fn corrupted_window_ack_is_rejected() {
+        let mut bytes = CEPPacket::WindowAck(5).serialize();
+        bytes[1] = 4;
+        assert!(matches!(CEPPacket::try_from(bytes), Err(CEPParseError::InvalidSequenceNumber)));
+    }
+
+    #[test]
+    fn compressed_data_is_decoded_transparently() {
+        let packet = CEPPacket::Data(vec![0xab; 1000]);
+        let bytes = packet.serialize_compressed();
+
+        assert_eq!(bytes[0], CEPPacketHeader::CompressedData as u8);
+        assert!(bytes.len() < 100);
+        assert_eq!(CEPPacket::try_from(bytes).unwrap(), packet);
+    }
+
+    #[test]
+    fn incompressible_data_is_sent_uncompressed() {
+        let packet = CEPPacket::Data(vec![1, 2, 3]);
+        assert_eq!(packet.serialize_compressed(), packet.serialize());
+    }
+
+    #[test]
+    fn oversized_compressed_data_is_rejected() {
+        let bytes =
+            CEPPacket::Data(vec![0; 2 * CEPPacket::MAXIMUM_DATA_LENGTH]).serialize_compressed();
+        assert!(matches!(CEPPacket::try_from(bytes), Err(CEPParseError::InvalidCompression)));
+    }
+
     #[test]
     fn invalid_crc_is_rejected() {
         assert!(matches!(
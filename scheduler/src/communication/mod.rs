@@ -1,14 +1,23 @@
+mod capabilities;
 mod cep;
+pub use capabilities::Capabilities;
 pub use cep::CEPPacket;
 pub mod socket;
+pub mod statistics;
 use self::cep::CEPParseError;
+use crate::watchdog::WatchdogHandle;
+pub use statistics::{ComStatistics, Counter, SharedComStatistics};
 use std::{
+    collections::BTreeMap,
     io::{Read, Write},
     time::Duration,
 };
 
 pub type ComResult<T> = Result<T, CommunicationError>;
 
+/// The version of the protocol spoken with the COBC. Incremented on incompatible changes.
+pub const PROTOCOL_VERSION: u8 = 1;
+
 pub trait CommunicationHandle: Read + Write {
     const INTEGRITY_ACK_TIMEOUT: Duration;
     const UNLIMITED_TIMEOUT: Duration;
@@ -17,9 +26,46 @@ pub trait CommunicationHandle: Read + Write {
 
     fn set_timeout(&mut self, timeout: Duration);
 
+    /// Returns the statistics this handle records into. Handles that do not keep any return `None`
+    fn statistics(&self) -> Option<&SharedComStatistics> {
+        None
+    }
+
+    /// Returns the capabilities negotiated with the COBC for the current session
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::NONE
+    }
+
+    /// Stores the capabilities negotiated with the COBC. Handles that do not support any optional
+    /// features ignore them.
+    fn set_capabilities(&mut self, _capabilities: Capabilities) {}
+
+    /// Waits up to `timeout` for data to arrive and returns wether there is any. Handles that can
+    /// not tell return true immediately.
+    fn wait_for_data(&mut self, _timeout: Duration) -> ComResult<bool> {
+        Ok(true)
+    }
+
+    /// Brings the underlying connection back into a known state after an IO error
+    fn reset(&mut self) -> ComResult<()> {
+        Ok(())
+    }
+
+    /// Increments the given counter, if this handle keeps statistics
+    fn count(&self, counter: Counter) {
+        if let Some(statistics) = self.statistics() {
+            statistics.lock().unwrap().increment(counter);
+        }
+    }
+
     fn send_packet(&mut self, packet: &CEPPacket) -> ComResult<()> {
-        let bytes = Vec::from(packet);
+        let bytes = if self.capabilities().contains(Capabilities::COMPRESSION) {
+            packet.serialize_compressed()
+        } else {
+            Vec::from(packet)
+        };
         self.write_all(&bytes)?;
+        self.count(Counter::PacketSent);
 
         if !(matches!(packet, CEPPacket::Data(_))) {
             return Ok(());
@@ -30,8 +76,11 @@ pub trait CommunicationHandle: Read + Write {
                 Ok(()) => return Ok(()),
                 Err(CommunicationError::NotAcknowledged) => {
                     log::warn!("Received NACK, retrying");
+                    self.count(Counter::NackReceived);
                     if i < Self::DATA_PACKET_RETRIES {
                         self.write_all(&bytes)?;
+                        self.count(Counter::PacketSent);
+                        self.count(Counter::Retransmit);
                     }
                 }
                 Err(e) => return Err(e),
@@ -54,21 +103,130 @@ pub trait CommunicationHandle: Read + Write {
         Ok(())
     }
 
+    /// Sends the given bytes like `send_multi_packet`, but keeps up to `window` packets in flight
+    /// instead of waiting for an ACK after every packet. Each packet carries a sequence number and
+    /// is selectively acknowledged by the receiver. Unacknowledged packets are retransmitted on a
+    /// NACK or after `INTEGRITY_ACK_TIMEOUT`. The packets are never compressed, even if
+    /// `Capabilities::COMPRESSION` was negotiated.
+    fn send_multi_packet_windowed(&mut self, bytes: &[u8], window: usize) -> ComResult<()> {
+        let chunks: Vec<&[u8]> = bytes.chunks(CEPPacket::MAXIMUM_DATA_LENGTH).collect();
+        if chunks.len() > usize::from(u16::MAX) + 1 {
+            log::error!("Too many packets for a windowed transfer: {}", chunks.len());
+            return Err(CommunicationError::PacketInvalidError);
+        }
+        let packet = |i: usize| CEPPacket::WindowData(u16::try_from(i).unwrap(), chunks[i].into());
+
+        let mut acknowledged = vec![false; chunks.len()];
+        let mut retries = vec![0; chunks.len()];
+        let mut base = 0; // first packet that is not yet acknowledged
+        let mut next = 0; // next packet that was not yet sent
+
+        while base < chunks.len() {
+            while next < chunks.len() && next < base + window.max(1) {
+                self.send_packet(&packet(next))?;
+                next += 1;
+            }
+
+            self.set_timeout(Self::INTEGRITY_ACK_TIMEOUT);
+            let response = self.receive_packet();
+            self.set_timeout(Self::UNLIMITED_TIMEOUT);
+
+            let to_retransmit: Vec<usize> = match response {
+                Ok(CEPPacket::WindowAck(seq)) if usize::from(seq) < next => {
+                    acknowledged[usize::from(seq)] = true;
+                    vec![]
+                }
+                Ok(CEPPacket::WindowNack(seq)) if usize::from(seq) < next => {
+                    log::warn!("Received NACK for packet {seq}, retrying");
+                    self.count(Counter::NackReceived);
+                    vec![usize::from(seq)]
+                }
+                Err(CommunicationError::TimedOut) => {
+                    log::warn!("Windowed transfer timed out, retransmitting window");
+                    (base..next).filter(|i| !acknowledged[*i]).collect()
+                }
+                Err(e @ CommunicationError::Io(_)) => return Err(e),
+                other => {
+                    log::warn!("Ignoring unexpected response during windowed transfer: {other:?}");
+                    vec![]
+                }
+            };
+
+            for i in to_retransmit {
+                retries[i] += 1;
+                if retries[i] >= Self::DATA_PACKET_RETRIES {
+                    log::error!("No ACK for packet {i} after {} retries, giving up", retries[i]);
+                    return Err(CommunicationError::PacketInvalidError);
+                }
+                self.send_packet(&packet(i))?;
+                self.count(Counter::Retransmit);
+            }
+
+            while base < chunks.len() && acknowledged[base] {
+                base += 1;
+            }
+        }
+
+        self.send_packet(&CEPPacket::Eof)?;
+        // a packet that was retransmitted because its ACK was late is acknowledged twice, so
+        // responses to the window may still arrive before the ACK of the EOF
+        for _ in 0..chunks.len() * Self::DATA_PACKET_RETRIES {
+            self.set_timeout(Self::INTEGRITY_ACK_TIMEOUT);
+            let response = self.receive_packet();
+            self.set_timeout(Self::UNLIMITED_TIMEOUT);
+
+            match response? {
+                CEPPacket::WindowAck(seq) | CEPPacket::WindowNack(seq) => {
+                    log::debug!("Ignoring stale response for packet {seq} after EOF");
+                }
+                CEPPacket::Ack => return Ok(()),
+                CEPPacket::Nack => return Err(CommunicationError::NotAcknowledged),
+                _ => return Err(CommunicationError::PacketInvalidError),
+            }
+        }
+
+        log::error!("Received no ACK for the EOF of a windowed transfer");
+        Err(CommunicationError::PacketInvalidError)
+    }
+
     fn receive_packet(&mut self) -> ComResult<CEPPacket> {
         for _ in 0..Self::DATA_PACKET_RETRIES {
             match CEPPacket::try_from_read(self) {
                 Ok(p @ CEPPacket::Data(_)) => {
+                    self.count(Counter::PacketReceived);
                     self.send_packet(&CEPPacket::Ack)?;
                     return Ok(p);
                 }
-                Ok(p) => return Ok(p),
+                Ok(p @ CEPPacket::WindowData(seq, _)) => {
+                    self.count(Counter::PacketReceived);
+                    self.send_packet(&CEPPacket::WindowAck(seq))?;
+                    return Ok(p);
+                }
+                Ok(p) => {
+                    self.count(Counter::PacketReceived);
+                    return Ok(p);
+                }
                 Err(CEPParseError::InvalidCRC) => {
                     log::warn!("Received data packet with invalid CRC; Retrying");
+                    self.count(Counter::CrcFailure);
                     self.send_packet(&CEPPacket::Nack)?;
                 }
+                Err(CEPParseError::InvalidCompression) => {
+                    log::warn!("Received data packet with invalid compression; Retrying");
+                    self.send_packet(&CEPPacket::Nack)?;
+                }
+                Err(CEPParseError::InvalidWindowCRC(seq)) => {
+                    log::warn!("Received window data packet {seq} with invalid CRC; Retrying");
+                    self.count(Counter::CrcFailure);
+                    self.send_packet(&CEPPacket::WindowNack(seq))?;
+                }
                 Err(e) => {
                     log::error!("Failed to read packet: {e:?}");
-                    return Err(e.into());
+                    let e = CommunicationError::from(e);
+                    if matches!(e, CommunicationError::TimedOut) {
+                        self.count(Counter::Timeout);
+                    }
+                    return Err(e);
                 }
             }
         }
@@ -80,8 +238,12 @@ pub trait CommunicationHandle: Read + Write {
         Err(CommunicationError::PacketInvalidError)
     }
 
+    /// Receives data packets until an EOF is received. Packets of a windowed transfer are
+    /// reassembled in the order of their sequence numbers. A transfer must not mix plain and
+    /// windowed data packets.
     fn receive_multi_packet(&mut self) -> ComResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        let mut window_buffer = BTreeMap::new();
 
         loop {
             let pack = self.receive_packet();
@@ -90,12 +252,16 @@ pub trait CommunicationHandle: Read + Write {
                 Ok(CEPPacket::Data(b)) => {
                     buffer.extend(b);
                 }
+                Ok(CEPPacket::WindowData(seq, b)) => {
+                    window_buffer.insert(seq, b);
+                }
                 Ok(CEPPacket::Eof) => {
                     break;
                 }
                 Err(e @ CommunicationError::Io(_)) => {
                     return Err(e);
                 }
+                // the timeout was already counted by `receive_packet`
                 Err(CommunicationError::TimedOut) => {
                     log::error!("Receive multipacket timed out");
                     return Err(CommunicationError::TimedOut);
@@ -104,8 +270,20 @@ pub trait CommunicationHandle: Read + Write {
                     log::error!("Received invalid data {:?}", e);
                     self.send_packet(&CEPPacket::Nack)?;
                 }
-            };
+            }
+        }
+
+        if !buffer.is_empty() && !window_buffer.is_empty() {
+            log::error!("Transfer mixes plain and windowed data packets");
+            self.send_packet(&CEPPacket::Nack)?;
+            return Err(CommunicationError::PacketInvalidError);
+        }
+        if window_buffer.keys().enumerate().any(|(i, seq)| usize::from(*seq) != i) {
+            log::error!("Windowed transfer is missing packets");
+            self.send_packet(&CEPPacket::Nack)?;
+            return Err(CommunicationError::PacketInvalidError);
         }
+        buffer.extend(window_buffer.into_values().flatten());
 
         self.send_packet(&CEPPacket::Ack)?;
         Ok(buffer)
@@ -125,13 +303,112 @@ pub trait CommunicationHandle: Read + Write {
     }
 }
 
-impl CommunicationHandle for Box<dyn serialport::SerialPort> {
+/// The handle used for the UART connection to the COBC. Wraps the serial port and records the
+/// link statistics.
+pub struct SerialComHandle {
+    port: Box<dyn serialport::SerialPort>,
+    statistics: SharedComStatistics,
+    capabilities: Capabilities,
+    watchdog: Option<WatchdogHandle>,
+    /// Reads never block longer than this, see `set_watchdog`
+    maximum_timeout: Duration,
+}
+
+impl SerialComHandle {
+    /// How often `wait_for_data` checks the UART for received bytes
+    const DATA_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    #[must_use]
+    pub fn new(port: Box<dyn serialport::SerialPort>, statistics: SharedComStatistics) -> Self {
+        Self {
+            port,
+            statistics,
+            capabilities: Capabilities::NONE,
+            watchdog: None,
+            maximum_timeout: Self::UNLIMITED_TIMEOUT,
+        }
+    }
+
+    /// Reports the progress of the UART to `watchdog`, which expects a check in every `timeout`.
+    /// The handle is idle only while `wait_for_data` waits for the next command. Within a command,
+    /// it checks in around every read, and reads are bounded to half of `timeout`, so that a COBC
+    /// that stops responding fails the command instead of blocking it forever.
+    pub fn set_watchdog(&mut self, watchdog: WatchdogHandle, timeout: Duration) {
+        self.watchdog = Some(watchdog);
+        self.maximum_timeout = timeout / 2;
+        let current = self.port.timeout();
+        self.set_timeout(current);
+    }
+
+    fn check_in(&self) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.check_in();
+        }
+    }
+}
+
+impl Read for SerialComHandle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.check_in();
+        let result = self.port.read(buf);
+        self.check_in();
+        result
+    }
+}
+
+impl Write for SerialComHandle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl CommunicationHandle for SerialComHandle {
+    #[allow(clippy::duration_suboptimal_units)]
     const INTEGRITY_ACK_TIMEOUT: Duration = Duration::from_millis(1000);
     /// Equivalent to 106 days, maximum allowed value due to library limitations (of all serialport libraries I found)
     const UNLIMITED_TIMEOUT: Duration = Duration::from_millis(9_223_372_035);
 
     fn set_timeout(&mut self, timeout: Duration) {
-        serialport::SerialPort::set_timeout(self.as_mut(), timeout).unwrap();
+        self.port.set_timeout(timeout.min(self.maximum_timeout)).unwrap();
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> ComResult<bool> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            // the next command may legitimately take forever
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.idle();
+            }
+            if self.port.bytes_to_read().map_err(std::io::Error::from)? > 0 {
+                return Ok(true);
+            }
+            if std::time::Instant::now() >= deadline {
+                return Ok(false);
+            }
+            std::thread::sleep(Self::DATA_POLL_INTERVAL);
+        }
+    }
+
+    fn reset(&mut self) -> ComResult<()> {
+        log::warn!("Discarding buffered UART data");
+        self.port.clear(serialport::ClearBuffer::All).map_err(std::io::Error::from)?;
+        Ok(())
+    }
+
+    fn statistics(&self) -> Option<&SharedComStatistics> {
+        Some(&self.statistics)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
     }
 }
 
@@ -179,15 +456,22 @@ mod tests {
     use self::cep::CEPPacketHeader;
 
     use super::*;
+    use crate::watchdog::Watchdog;
+    use serialport::TTYPort;
     use test_case::test_case;
 
     #[derive(Default)]
     pub struct TestComHandle {
         pub written_data: Vec<u8>,
         pub data_to_read: Vec<u8>,
+        pub statistics: SharedComStatistics,
+        pub capabilities: Capabilities,
     }
     impl Read for TestComHandle {
         fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.data_to_read.len() < buf.len() {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
             buf.copy_from_slice(&self.data_to_read[0..buf.len()]);
             self.data_to_read.drain(0..buf.len());
             Ok(buf.len())
@@ -207,6 +491,12 @@ mod tests {
         const INTEGRITY_ACK_TIMEOUT: Duration = Duration::from_millis(100);
         const UNLIMITED_TIMEOUT: Duration = Duration::MAX;
         fn set_timeout(&mut self, _timeout: Duration) {}
+        fn statistics(&self) -> Option<&SharedComStatistics> {
+            Some(&self.statistics)
+        }
+        fn capabilities(&self) -> Capabilities {
+            self.capabilities
+        }
     }
 
     #[test_case(CEPPacket::Ack)]
@@ -237,6 +527,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn data_is_compressed_if_negotiated() {
+        let mut com =
+            TestComHandle { capabilities: Capabilities::COMPRESSION, ..Default::default() };
+        com.data_to_read.append(&mut CEPPacket::Ack.serialize());
+
+        let packet = CEPPacket::Data(vec![7; 500]);
+        com.send_packet(&packet).unwrap();
+
+        assert_eq!(com.written_data, packet.serialize_compressed());
+        assert!(com.written_data.len() < 500);
+    }
+
     #[test]
     fn retry_on_nack() {
         let mut com = TestComHandle::default();
@@ -248,6 +551,12 @@ mod tests {
 
         assert_eq!(com.written_data, CEPPacket::Data(vec![1, 2, 3]).serialize().repeat(3));
         assert!(com.data_to_read.is_empty());
+
+        let counters = com.statistics.lock().unwrap().session;
+        assert_eq!(counters.packets_sent, 3);
+        assert_eq!(counters.packets_received, 3);
+        assert_eq!(counters.nacks_received, 2);
+        assert_eq!(counters.retransmits, 2);
     }
 
     #[test]
@@ -301,6 +610,17 @@ mod tests {
             CEPPacket::Nack.serialize().repeat(TestComHandle::DATA_PACKET_RETRIES - 1);
         expected.append(&mut CEPPacket::Ack.serialize());
         assert_eq!(com.written_data, expected);
+
+        let counters = com.statistics.lock().unwrap().session;
+        assert_eq!(
+            counters.crc_failures,
+            u32::try_from(TestComHandle::DATA_PACKET_RETRIES - 1).unwrap()
+        );
+        assert_eq!(counters.packets_received, 1);
+        assert_eq!(
+            counters.packets_sent,
+            u32::try_from(TestComHandle::DATA_PACKET_RETRIES).unwrap()
+        );
     }
 
     #[test]
@@ -338,4 +658,176 @@ mod tests {
         assert!(com.data_to_read.is_empty());
         assert_eq!(com.written_data, CEPPacket::Ack.serialize().repeat(chunks.len() + 1));
     }
+
+    #[test]
+    fn multi_packet_timeout_is_counted() {
+        let mut com = TestComHandle::default();
+        com.data_to_read.append(&mut CEPPacket::Data(vec![1, 2, 3]).serialize());
+
+        assert!(matches!(com.receive_multi_packet(), Err(CommunicationError::TimedOut)));
+        let counters = com.statistics.lock().unwrap().session;
+        assert_eq!(counters.timeouts, 1);
+        assert_eq!(counters.packets_received, 1);
+    }
+
+    #[test]
+    fn windowed_multi_packet_is_sent_correctly() {
+        let mut com = TestComHandle::default();
+
+        let data = vec![123u8; 2 * CEPPacket::MAXIMUM_DATA_LENGTH + 50];
+        let chunks: Vec<_> = data.chunks(CEPPacket::MAXIMUM_DATA_LENGTH).collect();
+        for seq in 0..3 {
+            com.data_to_read.append(&mut CEPPacket::WindowAck(seq).serialize());
+        }
+        com.data_to_read.append(&mut CEPPacket::Ack.serialize());
+
+        com.send_multi_packet_windowed(&data, 2).unwrap();
+
+        assert!(com.data_to_read.is_empty());
+        let mut expected = Vec::new();
+        for (seq, c) in (0..).zip(&chunks) {
+            expected.append(&mut CEPPacket::WindowData(seq, c.to_vec()).serialize());
+        }
+        expected.append(&mut CEPPacket::Eof.serialize());
+        assert_eq!(com.written_data, expected);
+    }
+
+    #[test]
+    fn windowed_multi_packet_retransmits_on_nack() {
+        let mut com = TestComHandle::default();
+
+        let data = vec![1u8; CEPPacket::MAXIMUM_DATA_LENGTH + 1];
+        com.data_to_read.append(&mut CEPPacket::WindowNack(0).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowAck(1).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowAck(0).serialize());
+        com.data_to_read.append(&mut CEPPacket::Ack.serialize());
+
+        com.send_multi_packet_windowed(&data, 4).unwrap();
+
+        let first = CEPPacket::WindowData(0, data[..CEPPacket::MAXIMUM_DATA_LENGTH].to_vec());
+        let second = CEPPacket::WindowData(1, vec![1]);
+        let mut expected = first.serialize();
+        expected.append(&mut second.serialize());
+        expected.append(&mut first.serialize());
+        expected.append(&mut CEPPacket::Eof.serialize());
+        assert_eq!(com.written_data, expected);
+
+        let counters = com.statistics.lock().unwrap().session;
+        assert_eq!(counters.nacks_received, 1);
+        assert_eq!(counters.retransmits, 1);
+    }
+
+    #[test]
+    fn windowed_multi_packet_ignores_duplicate_ack_after_eof() {
+        let mut com = TestComHandle::default();
+
+        let data = vec![1u8; 10];
+        com.data_to_read.append(&mut CEPPacket::WindowNack(0).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowAck(0).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowAck(0).serialize()); // of the retransmission
+        com.data_to_read.append(&mut CEPPacket::Ack.serialize());
+
+        com.send_multi_packet_windowed(&data, 4).unwrap();
+
+        assert!(com.data_to_read.is_empty());
+        assert!(com.written_data.ends_with(&CEPPacket::Eof.serialize()));
+    }
+
+    #[test]
+    fn windowed_multi_packet_is_reassembled_in_order() {
+        let mut com = TestComHandle::default();
+
+        com.data_to_read.append(&mut CEPPacket::WindowData(1, vec![3, 4]).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowData(0, vec![1, 2]).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowData(2, vec![5]).serialize());
+        com.data_to_read.append(&mut CEPPacket::Eof.serialize());
+
+        assert_eq!(com.receive_multi_packet().unwrap(), vec![1, 2, 3, 4, 5]);
+        let mut expected = CEPPacket::WindowAck(1).serialize();
+        expected.append(&mut CEPPacket::WindowAck(0).serialize());
+        expected.append(&mut CEPPacket::WindowAck(2).serialize());
+        expected.append(&mut CEPPacket::Ack.serialize());
+        assert_eq!(com.written_data, expected);
+    }
+
+    #[test]
+    fn windowed_multi_packet_with_gap_is_rejected() {
+        let mut com = TestComHandle::default();
+
+        com.data_to_read.append(&mut CEPPacket::WindowData(0, vec![1]).serialize());
+        com.data_to_read.append(&mut CEPPacket::WindowData(2, vec![3]).serialize());
+        com.data_to_read.append(&mut CEPPacket::Eof.serialize());
+
+        assert!(matches!(com.receive_multi_packet(), Err(CommunicationError::PacketInvalidError)));
+        assert!(com.written_data.ends_with(&CEPPacket::Nack.serialize()));
+    }
+
+    #[test]
+    fn mixed_multi_packet_is_rejected() {
+        let mut com = TestComHandle::default();
+
+        com.data_to_read.append(&mut CEPPacket::WindowData(0, vec![1]).serialize());
+        com.data_to_read.append(&mut CEPPacket::Data(vec![2]).serialize());
+        com.data_to_read.append(&mut CEPPacket::Eof.serialize());
+
+        assert!(matches!(com.receive_multi_packet(), Err(CommunicationError::PacketInvalidError)));
+        assert!(com.written_data.ends_with(&CEPPacket::Nack.serialize()));
+    }
+
+    #[test]
+    fn packet_with_invalid_compression_is_retried() {
+        let mut com = TestComHandle::default();
+        let invalid =
+            CEPPacket::Data(vec![0; 2 * CEPPacket::MAXIMUM_DATA_LENGTH]).serialize_compressed();
+        com.data_to_read.extend(invalid);
+        com.data_to_read.append(&mut CEPPacket::Data(vec![1, 2]).serialize());
+
+        assert_eq!(com.receive_packet().unwrap(), CEPPacket::Data(vec![1, 2]));
+        let mut expected = CEPPacket::Nack.serialize();
+        expected.append(&mut CEPPacket::Ack.serialize());
+        assert_eq!(com.written_data, expected);
+    }
+
+    fn serial_handle(watchdog: &Watchdog, timeout: Duration) -> (TTYPort, SerialComHandle) {
+        let (cobc, edu) = serialport::TTYPort::pair().unwrap();
+        let mut com = SerialComHandle::new(Box::new(edu), SharedComStatistics::default());
+        com.set_timeout(SerialComHandle::UNLIMITED_TIMEOUT);
+        com.set_watchdog(watchdog.register("command", timeout), timeout);
+        (cobc, com)
+    }
+
+    #[test]
+    fn serial_handle_checks_in_during_a_transfer() {
+        let watchdog = Watchdog::default();
+        let (mut cobc, mut com) = serial_handle(&watchdog, Duration::from_millis(200));
+
+        let receiver = std::thread::spawn(move || com.receive_multi_packet());
+        for i in 0..5 {
+            cobc.write_all(&CEPPacket::Data(vec![i]).serialize()).unwrap();
+            std::thread::sleep(Duration::from_millis(80));
+            assert!(watchdog.silent_threads().is_empty());
+        }
+        cobc.write_all(&CEPPacket::Eof.serialize()).unwrap();
+        assert_eq!(receiver.join().unwrap().unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn serial_handle_does_not_block_within_a_command() {
+        let watchdog = Watchdog::default();
+        let (_cobc, mut com) = serial_handle(&watchdog, Duration::from_millis(200));
+
+        let start = std::time::Instant::now();
+        assert!(matches!(com.receive_packet(), Err(CommunicationError::TimedOut)));
+        assert!(start.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn serial_handle_is_idle_between_commands() {
+        let watchdog = Watchdog::default();
+        let (_cobc, mut com) = serial_handle(&watchdog, Duration::from_millis(50));
+
+        assert!(!com.wait_for_data(Duration::from_millis(100)).unwrap());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(watchdog.silent_threads().is_empty());
+    }
 }
@@ -1,55 +1,120 @@
+use crate::watchdog::{Watchdog, WatchdogHandle};
 use std::{
     io::{BufRead, BufReader, Write},
     os::unix::net::{UnixListener, UnixStream},
-    path::Path,
-    str::FromStr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-pub struct UnixSocketParser {
+/// A line based request/response server on a unix socket. Any number of clients may be connected
+/// at the same time, each of them is served by its own thread. Every line a client sends is passed
+/// to the handler, whose answer is written back as a single line.
+pub struct UnixSocketServer {
     listener: UnixListener,
-    connection: Option<BufReader<UnixStream>>,
+    path: PathBuf,
+    shutdown: Arc<AtomicBool>,
 }
 
-impl UnixSocketParser {
-    pub fn new(path: &str) -> std::io::Result<Self> {
-        let _ = std::fs::remove_file(path);
-        Ok(Self { listener: UnixListener::bind(path)?, connection: None })
+impl UnixSocketServer {
+    pub fn bind(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let _ = std::fs::remove_file(&path);
+        Ok(Self { listener: UnixListener::bind(&path)?, path, shutdown: Arc::default() })
     }
 
-    pub fn read_object<T: FromStr>(&mut self) -> std::io::Result<T> {
-        if self.connection.is_none() {
-            let (stream, _) = self.listener.accept()?;
-            self.connection = Some(BufReader::new(stream));
-        }
-
-        let con = self.connection.as_mut().unwrap();
-        let mut line = String::new();
-        con.read_line(&mut line)?;
+    /// Returns a handle that stops `serve`. Clients can not stop the server themselves.
+    #[cfg(test)]
+    fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle { shutdown: self.shutdown.clone(), path: self.path.clone() }
+    }
 
-        if !line.ends_with('\n') || line.is_empty() {
-            self.connection.take();
-            return Err(std::io::ErrorKind::ConnectionAborted.into());
+    /// Serves clients until the server is stopped, which is only done by tests. The threads of the
+    /// clients are monitored by `watchdog` under the given name, while they are handling a request.
+    pub fn serve<H>(self, handler: H, watchdog: &Watchdog, name: &'static str, timeout: Duration)
+    where
+        H: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+
+        for stream in self.listener.incoming() {
+            if self.shutdown.load(Ordering::Relaxed) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Could not accept client on {:?}: {e}", self.path);
+                    continue;
+                }
+            };
+
+            let client = Client {
+                stream,
+                check_in: watchdog.register(name, timeout),
+                path: self.path.clone(),
+            };
+            let handler = handler.clone();
+            std::thread::spawn(move || client.serve(handler.as_ref()));
         }
+    }
+}
 
-        if line == Self::SHUTDOWN_STRING {
-            return Err(std::io::ErrorKind::Other.into());
-        }
+/// Stops a [`UnixSocketServer`] from accepting new clients
+#[cfg(test)]
+struct ShutdownHandle {
+    shutdown: Arc<AtomicBool>,
+    path: PathBuf,
+}
 
-        T::from_str(line.trim_end()).map_err(|_| std::io::ErrorKind::InvalidData.into())
+#[cfg(test)]
+impl ShutdownHandle {
+    fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // wake up the accepting thread, so that it notices the shutdown
+        let _ = UnixStream::connect(&self.path);
     }
+}
+
+struct Client {
+    stream: UnixStream,
+    check_in: WatchdogHandle,
+    path: PathBuf,
+}
 
-    const SHUTDOWN_STRING: &'static str = "shutdown\n";
-    pub fn _shutdown(path: impl AsRef<Path>) -> std::io::Result<()> {
-        let mut stream = UnixStream::connect(path)?;
-        stream.write_all(Self::SHUTDOWN_STRING.as_bytes())?;
+impl Client {
+    fn serve(mut self, handler: &impl Fn(&str) -> String) {
+        let mut reader = match self.stream.try_clone() {
+            Ok(stream) => BufReader::new(stream),
+            Err(e) => {
+                log::warn!("Could not read from client on {:?}: {e}", self.path);
+                return;
+            }
+        };
+
+        loop {
+            // waiting for the next request may take forever
+            self.check_in.idle();
+            let mut line = String::new();
+            if reader.read_line(&mut line).is_err() || !line.ends_with('\n') {
+                break; // the client disconnected, possibly in the middle of a line
+            }
+            self.check_in.check_in();
 
-        Ok(())
+            let response = handler(line.trim_end());
+            if writeln!(self.stream, "{response}").is_err() {
+                break;
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::atomic::AtomicUsize;
 
     use super::*;
 
@@ -61,98 +126,91 @@ mod tests {
         path
     }
 
-    #[test]
-    fn can_shutdown() {
-        let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
-
-        UnixSocketParser::_shutdown(&path).unwrap();
+    /// Starts a server that answers every line with its length
+    fn start_server(path: &str) -> (std::thread::JoinHandle<()>, ShutdownHandle) {
+        let server = UnixSocketServer::bind(path).unwrap();
+        let shutdown = server.shutdown_handle();
+        let thread = std::thread::spawn(move || {
+            let handler = |line: &str| line.len().to_string();
+            server.serve(handler, &Watchdog::default(), "test", Duration::from_secs(1));
+        });
+        (thread, shutdown)
+    }
 
-        assert_eq!(std::io::ErrorKind::Other, rx.read_object::<i32>().unwrap_err().kind());
+    fn request(stream: &mut BufReader<UnixStream>, line: &str) -> String {
+        writeln!(stream.get_mut(), "{line}").unwrap();
+        let mut response = String::new();
+        stream.read_line(&mut response).unwrap();
+        response
     }
 
     #[test]
-    fn can_parse_single_value() {
+    fn can_shutdown() {
         let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
-
-        let mut stream = UnixStream::connect(&path).unwrap();
-        writeln!(stream, "1234").unwrap();
+        let (server, shutdown) = start_server(&path);
 
-        assert_eq!(1234, rx.read_object::<i32>().unwrap());
-
-        UnixSocketParser::_shutdown(path).unwrap();
+        shutdown.shutdown();
+        server.join().unwrap();
     }
 
     #[test]
-    fn can_parse_multiple_values() {
-        const REPS: usize = 100;
-
+    fn clients_can_not_shutdown() {
         let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
-
-        let mut stream = UnixStream::connect(&path).unwrap();
-        for i in 0..REPS {
-            writeln!(stream, "{i}").unwrap();
-        }
+        let (server, shutdown) = start_server(&path);
 
-        for i in 0..REPS {
-            assert_eq!(i, rx.read_object::<usize>().unwrap());
-        }
+        let mut stream = BufReader::new(UnixStream::connect(&path).unwrap());
+        assert_eq!(request(&mut stream, "shutdown"), "8\n");
+        let mut stream = BufReader::new(UnixStream::connect(&path).unwrap());
+        assert_eq!(request(&mut stream, "123"), "3\n");
 
-        UnixSocketParser::_shutdown(path).unwrap();
+        shutdown.shutdown();
+        server.join().unwrap();
     }
 
     #[test]
-    fn can_reconnect_multiple_times() {
+    fn responds_to_multiple_requests() {
         let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
-
-        for i in 0..10 {
-            {
-                let mut stream = UnixStream::connect(&path).unwrap();
-                writeln!(stream, "{i}").unwrap();
-            }
+        let (server, shutdown) = start_server(&path);
 
-            assert_eq!(i, rx.read_object::<u8>().unwrap());
-            assert_eq!(
-                rx.read_object::<u8>().unwrap_err().kind(),
-                std::io::ErrorKind::ConnectionAborted
-            );
+        let mut stream = BufReader::new(UnixStream::connect(&path).unwrap());
+        for i in 0..100 {
+            assert_eq!(request(&mut stream, &"a".repeat(i)), format!("{i}\n"));
         }
 
-        UnixSocketParser::_shutdown(path).unwrap();
+        shutdown.shutdown();
+        server.join().unwrap();
     }
 
     #[test]
-    fn can_deal_with_invalid_data() {
+    fn serves_concurrent_clients() {
         let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
-
-        let mut stream = UnixStream::connect(&path).unwrap();
-        writeln!(stream, "invalid").unwrap();
-        assert_eq!(std::io::ErrorKind::InvalidData, rx.read_object::<u64>().unwrap_err().kind());
+        let (server, shutdown) = start_server(&path);
 
-        writeln!(stream, "123").unwrap();
-        assert_eq!(123, rx.read_object::<u64>().unwrap());
+        let mut first = BufReader::new(UnixStream::connect(&path).unwrap());
+        let mut second = BufReader::new(UnixStream::connect(&path).unwrap());
+        assert_eq!(request(&mut second, "12"), "2\n");
+        assert_eq!(request(&mut first, "123"), "3\n");
+        drop(second);
+        assert_eq!(request(&mut first, "1234"), "4\n");
 
-        UnixSocketParser::_shutdown(path).unwrap();
+        shutdown.shutdown();
+        server.join().unwrap();
     }
 
     #[test]
     fn can_reconnect_after_midline_abort() {
         let path = get_unique_tmp_path();
-        let mut rx = UnixSocketParser::new(&path).unwrap();
+        let (server, shutdown) = start_server(&path);
 
         {
             let mut stream = UnixStream::connect(&path).unwrap();
             write!(stream, "1234").unwrap();
         }
 
-        let mut stream = UnixStream::connect(&path).unwrap();
-        writeln!(stream, "5647").unwrap();
+        let mut stream = BufReader::new(UnixStream::connect(&path).unwrap());
+        assert_eq!(request(&mut stream, "5647"), "4\n");
 
-        rx.read_object::<u32>().unwrap_err();
-        assert_eq!(5647, rx.read_object::<u32>().unwrap());
+        shutdown.shutdown();
+        server.join().unwrap();
     }
 }
@@ -0,0 +1,215 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How often `save_if_due` persists the lifetime counters, to spare the flash of the EDU
+const SAVE_INTERVAL: Duration = Duration::from_mins(1);
+
+/// This type allows the statistics to be shared between a `CommunicationHandle` and other threads
+pub type SharedComStatistics = Arc<Mutex<ComStatistics>>;
+
+/// The different events that are counted by [`ComStatistics`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    PacketSent,
+    PacketReceived,
+    CrcFailure,
+    NackReceived,
+    Timeout,
+    Retransmit,
+}
+
+/// A set of counters describing the quality of the link to the COBC
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ComCounters {
+    pub packets_sent: u32,
+    pub packets_received: u32,
+    pub crc_failures: u32,
+    pub nacks_received: u32,
+    pub timeouts: u32,
+    pub retransmits: u32,
+}
+
+impl ComCounters {
+    /// The length of the counters when serialized for the COBC
+    pub const SERIALIZED_LENGTH: usize = 6 * 4;
+
+    fn increment(&mut self, counter: Counter) {
+        let value = match counter {
+            Counter::PacketSent => &mut self.packets_sent,
+            Counter::PacketReceived => &mut self.packets_received,
+            Counter::CrcFailure => &mut self.crc_failures,
+            Counter::NackReceived => &mut self.nacks_received,
+            Counter::Timeout => &mut self.timeouts,
+            Counter::Retransmit => &mut self.retransmits,
+        };
+        *value = value.saturating_add(1);
+    }
+}
+
+impl From<ComCounters> for Vec<u8> {
+    fn from(value: ComCounters) -> Self {
+        let mut v = Vec::with_capacity(ComCounters::SERIALIZED_LENGTH);
+        v.extend(value.packets_sent.to_le_bytes());
+        v.extend(value.packets_received.to_le_bytes());
+        v.extend(value.crc_failures.to_le_bytes());
+        v.extend(value.nacks_received.to_le_bytes());
+        v.extend(value.timeouts.to_le_bytes());
+        v.extend(value.retransmits.to_le_bytes());
+        v
+    }
+}
+
+impl Display for ComCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sent={} received={} crc_failures={} nacks={} timeouts={} retransmits={}",
+            self.packets_sent,
+            self.packets_received,
+            self.crc_failures,
+            self.nacks_received,
+            self.timeouts,
+            self.retransmits
+        )
+    }
+}
+
+/// Keeps the counters of the current session, as well as the ones accumulated over the lifetime of
+/// the scheduler. Only the lifetime counters are persisted.
+#[derive(Debug, Default)]
+pub struct ComStatistics {
+    pub session: ComCounters,
+    pub lifetime: ComCounters,
+    path: Option<PathBuf>,
+    saved: Option<Instant>,
+}
+
+impl ComStatistics {
+    /// Loads the lifetime counters from the given file. If it does not exist or contains invalid
+    /// data, the lifetime counters start at zero.
+    #[must_use]
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let lifetime = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Self {
+            session: ComCounters::default(),
+            lifetime,
+            path: Some(path.as_ref().into()),
+            saved: Some(Instant::now()),
+        }
+    }
+
+    #[must_use]
+    pub fn shared(self) -> SharedComStatistics {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn increment(&mut self, counter: Counter) {
+        self.session.increment(counter);
+        self.lifetime.increment(counter);
+    }
+
+    /// Like `save`, but only if the counters were not saved within the last `SAVE_INTERVAL`.
+    /// Counts since then are lost if the EDU loses power.
+    pub fn save_if_due(&mut self) -> std::io::Result<()> {
+        if self.saved.is_some_and(|saved| saved.elapsed() < SAVE_INTERVAL) {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Writes the lifetime counters into the file they were loaded from. The file is replaced
+    /// atomically, so a power loss can not leave it half-written.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        self.saved = Some(Instant::now());
+
+        let serialized = toml::to_string(&self.lifetime)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    /// Serializes the session counters, followed by the lifetime counters
+    #[must_use]
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut v = Vec::from(self.session);
+        v.extend(Vec::from(self.lifetime));
+        v
+    }
+}
+
+impl Display for ComStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "session: {}; lifetime: {}", self.session, self.lifetime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifetime_counters_are_persisted() {
+        let path = "__com_statistics_persisted";
+        let _ = std::fs::remove_file(path);
+
+        let mut statistics = ComStatistics::open(path);
+        statistics.increment(Counter::PacketSent);
+        statistics.increment(Counter::CrcFailure);
+        statistics.save().unwrap();
+
+        let mut statistics = ComStatistics::open(path);
+        assert_eq!(statistics.session, ComCounters::default());
+        assert_eq!(statistics.lifetime.packets_sent, 1);
+        assert_eq!(statistics.lifetime.crc_failures, 1);
+
+        statistics.increment(Counter::PacketSent);
+        assert_eq!(statistics.session.packets_sent, 1);
+        assert_eq!(statistics.lifetime.packets_sent, 2);
+        assert!(!Path::new("__com_statistics_persisted.tmp").exists());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn invalid_file_is_ignored() {
+        let path = "__com_statistics_invalid";
+        std::fs::write(path, "not toml {").unwrap();
+
+        assert_eq!(ComStatistics::open(path).lifetime, ComCounters::default());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn counters_are_saved_periodically() {
+        let path = "__com_statistics_periodically";
+        let _ = std::fs::remove_file(path);
+
+        let mut statistics = ComStatistics::open(path);
+        statistics.increment(Counter::PacketSent);
+        statistics.save_if_due().unwrap();
+        assert!(!Path::new(path).exists());
+
+        statistics.saved = Instant::now().checked_sub(SAVE_INTERVAL);
+        statistics.save_if_due().unwrap();
+        assert_eq!(ComStatistics::open(path).lifetime.packets_sent, 1);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
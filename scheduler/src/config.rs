@@ -0,0 +1,685 @@
+use crate::{gpio::GpioBackendKind, logging::LogFilter};
+use ed25519_dalek::VerifyingKey;
+use log::{LevelFilter, ParseLevelError};
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The highest GPIO number of the BCM2711
+const MAXIMUM_PIN: u8 = 57;
+/// Above this frequency, the heartbeat toggle time rounds down to 0ms
+const MAXIMUM_HEARTBEAT_FREQ: u64 = 500;
+/// Unix socket paths are limited by the size of `sun_path`
+const MAXIMUM_SOCKET_PATH_LENGTH: usize = 107;
+
+/// All keys that may appear in `config.toml`
+const FIELDS: &[&str] = &[
+    "uart",
+    "baudrate",
+    "heartbeat_pin",
+    "update_pin",
+    "heartbeat_freq",
+    "socket",
+    "log_level",
+    "log_filters",
+    "log_path",
+    "log_max_size",
+    "log_files",
+    "archives_path",
+    "data_path",
+    "events_path",
+    "fault_log_path",
+    "statistics_path",
+    "maximum_file_size",
+    "command_timeout_ms",
+    "event_send_tries",
+    "terminate_timeout_ms",
+    "python",
+    "update_health_window_s",
+    "trusted_keys",
+    "watchdog_timeout_s",
+    "gpio_backend",
+    "gpio_chip",
+    "update_pin_file",
+    "store_path",
+    "store_quota",
+    "store_max_keys",
+    "api_socket",
+    "coalesce_socket_events",
+    "socket_event_rate_limit",
+];
+
+/// Keys that are only read at startup, so changing them requires a restart
+const RESTART_FIELDS: &[&str] = &[
+    "uart",
+    "baudrate",
+    "heartbeat_pin",
+    "update_pin",
+    "heartbeat_freq",
+    "socket",
+    "log_path",
+    "log_max_size",
+    "log_files",
+    "archives_path",
+    "data_path",
+    "events_path",
+    "fault_log_path",
+    "statistics_path",
+    "update_health_window_s",
+    "watchdog_timeout_s",
+    "gpio_backend",
+    "gpio_chip",
+    "update_pin_file",
+    "api_socket",
+];
+
+/// Keys that can not be changed by the COBC, as that would allow to switch off security measures,
+/// to run an arbitrary executable instead of the interpreter, or to remove and overwrite arbitrary
+/// files through the paths and sockets the scheduler writes to
+const PROTECTED_FIELDS: &[&str] = &[
+    "trusted_keys",
+    "python",
+    "uart",
+    "socket",
+    "api_socket",
+    "log_path",
+    "archives_path",
+    "data_path",
+    "events_path",
+    "fault_log_path",
+    "statistics_path",
+    "gpio_chip",
+    "update_pin_file",
+    "store_path",
+];
+
+/// The settings read from `config.toml`. Keys that are missing take their default value.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Configuration {
+    pub uart: String,
+    pub baudrate: u32,
+    pub heartbeat_pin: u8,
+    pub update_pin: u8,
+    /// In Hz
+    pub heartbeat_freq: u64,
+    pub socket: String,
+    /// The default log level, e.g. `info`
+    pub log_level: String,
+    /// Per-module log levels, e.g. `communication=debug`
+    pub log_filters: Vec<String>,
+    pub log_path: PathBuf,
+    /// In bytes, once the log grows beyond this size it is rotated
+    pub log_max_size: u64,
+    /// The number of rotated logs that are kept
+    pub log_files: u8,
+    /// Stored student programs, one directory per program id
+    pub archives_path: PathBuf,
+    /// Received archives, student program output and results
+    pub data_path: PathBuf,
+    pub events_path: PathBuf,
+    pub fault_log_path: PathBuf,
+    pub statistics_path: PathBuf,
+    /// In bytes, larger result files are truncated
+    pub maximum_file_size: usize,
+    /// How long the COBC may take to acknowledge the end of a command
+    pub command_timeout_ms: u64,
+    /// How often an event is sent to the COBC before it is dropped
+    pub event_send_tries: u32,
+    /// How long a student program may take to stop once it was asked to
+    pub terminate_timeout_ms: u64,
+    /// The interpreter student programs are run with
+    pub python: String,
+    /// How long the critical threads of an installed update have to stay responsive before it is
+    /// confirmed. If any of them goes silent before, it is rolled back.
+    pub update_health_window_s: u64,
+    /// Hex encoded Ed25519 public keys. If any are given, only archives and updates signed by one of
+    /// them are accepted.
+    pub trusted_keys: Vec<String>,
+    /// If a critical thread does not make progress for this long, the heartbeat stops
+    pub watchdog_timeout_s: u64,
+    /// How the heartbeat and update pin are accessed
+    pub gpio_backend: GpioBackendKind,
+    /// The character device used by the `cdev` backend
+    pub gpio_chip: PathBuf,
+    /// If not empty, pending events are signalled by creating this file instead of setting the
+    /// update pin
+    pub update_pin_file: PathBuf,
+    /// Persistent key-value stores of the student programs, one directory per program id
+    pub store_path: PathBuf,
+    /// In bytes, the maximum size of the values in the store of a single program
+    pub store_quota: u64,
+    /// The maximum number of keys in the store of a single program
+    pub store_max_keys: usize,
+    /// The socket running student programs can use, see `student_api::respond`, which is served
+    /// by `UnixSocketServer::serve`
+    pub api_socket: String,
+    /// If set, an event from the socket replaces the last queued event if it is of the same group,
+    /// e.g. dosimeter/off replaces dosimeter/on
+    pub coalesce_socket_events: bool,
+    /// How many events of the same kind the socket accepts per minute, 0 disables the limit. Also
+    /// limits the custom events of each run of a student program on the API socket.
+    pub socket_event_rate_limit: u32,
+    /// The file the configuration was loaded from, changes are persisted there
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            uart: "/dev/serial0".to_string(),
+            baudrate: 921_600,
+            heartbeat_pin: 34,
+            update_pin: 35,
+            heartbeat_freq: 10,
+            socket: "/tmp/scheduler_socket".to_string(),
+            log_level: "info".to_string(),
+            log_filters: Vec::new(),
+            log_path: "log".into(),
+            log_max_size: 1_000_000,
+            log_files: 4,
+            archives_path: "./archives".into(),
+            data_path: "./data".into(),
+            events_path: "events".into(),
+            fault_log_path: "faults".into(),
+            statistics_path: "com_statistics".into(),
+            maximum_file_size: 1_000_000,
+            command_timeout_ms: 1000,
+            event_send_tries: 5,
+            terminate_timeout_ms: 2000,
+            python: "python".to_string(),
+            update_health_window_s: 600,
+            trusted_keys: Vec::new(),
+            watchdog_timeout_s: 60,
+            gpio_backend: GpioBackendKind::Rppal,
+            gpio_chip: "/dev/gpiochip0".into(),
+            update_pin_file: PathBuf::new(),
+            store_path: "./store".into(),
+            store_quota: 65_536,
+            store_max_keys: 64,
+            api_socket: "/tmp/scheduler_api".to_string(),
+            coalesce_socket_events: true,
+            socket_event_rate_limit: 10,
+            path: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Could not read config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse config: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Unknown field {0:?}")]
+    UnknownField(String),
+    #[error("Invalid value for {field}: {reason}")]
+    InvalidValue { field: &'static str, reason: String },
+}
+
+impl Configuration {
+    /// Reads and validates the configuration in the given file, returning every problem that was
+    /// found
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Vec<ConfigError>> {
+        let s = std::fs::read_to_string(&path).map_err(|e| vec![e.into()])?;
+        let config = Self::from_toml(&s)?;
+        Ok(Self { path: Some(path.as_ref().into()), ..config })
+    }
+
+    /// Like `load`, but only the keys that are unknown or invalid are replaced by their default,
+    /// so that a single mistake does not undo the whole configuration. The problems that were
+    /// found are returned along with it.
+    #[must_use]
+    pub fn load_lenient(path: impl AsRef<Path>) -> (Self, Vec<ConfigError>) {
+        let (config, errors) = match std::fs::read_to_string(&path) {
+            Ok(s) => Self::from_toml_lenient(&s),
+            Err(e) => (Self::default(), vec![e.into()]),
+        };
+        (Self { path: Some(path.as_ref().into()), ..config }, errors)
+    }
+
+    /// Parses a configuration like `from_toml`, but keys that are unknown or invalid take their
+    /// default value instead of failing the whole configuration
+    #[must_use]
+    pub fn from_toml_lenient(s: &str) -> (Self, Vec<ConfigError>) {
+        let table: toml::Table = match s.parse() {
+            Ok(table) => table,
+            Err(e) => return (Self::default(), vec![ConfigError::Parse(e)]),
+        };
+
+        let mut errors = Vec::new();
+        let mut valid = toml::Table::new();
+        for (key, value) in table {
+            let Some(&field) = FIELDS.iter().find(|f| **f == key) else {
+                errors.push(ConfigError::UnknownField(key));
+                continue;
+            };
+            let single = toml::Table::from_iter([(key.clone(), value.clone())]);
+            match toml::Value::Table(single).try_into::<Configuration>() {
+                Ok(_) => {
+                    valid.insert(key, value);
+                }
+                Err(e) => errors.push(ConfigError::InvalidValue { field, reason: e.to_string() }),
+            }
+        }
+
+        // values may only be invalid in combination, so validate until no key has to be dropped
+        loop {
+            let config: Configuration =
+                toml::Value::Table(valid.clone()).try_into().expect("every key was checked");
+            let problems = config.validate();
+            if problems.is_empty() {
+                return (config, errors);
+            }
+
+            let mut dropped = false;
+            for problem in problems {
+                if let ConfigError::InvalidValue { field, .. } = &problem {
+                    dropped |= valid.remove(*field).is_some();
+                }
+                errors.push(problem);
+            }
+            if !dropped {
+                // a configured key clashes with the default of another one
+                return (Self::default(), errors);
+            }
+        }
+    }
+
+    /// Returns the value of a single key, formatted as a TOML assignment
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<String> {
+        let table = toml::Table::try_from(self).ok()?;
+        table.get(key).map(|value| format!("{key} = {value}"))
+    }
+
+    /// Changes a single key, given as a TOML assignment like `heartbeat_freq = 20`. The resulting
+    /// configuration is validated and persisted into the file it was loaded from.
+    ///
+    /// Returns wether a restart is needed for the change to take effect. In that case, the
+    /// configuration in memory is left untouched.
+    pub fn set(&mut self, assignment: &str) -> Result<bool, Vec<ConfigError>> {
+        let assignment: toml::Table =
+            assignment.parse().map_err(|e| vec![ConfigError::Parse(e)])?;
+        let mut entries = assignment.into_iter();
+        let (Some((key, value)), None) = (entries.next(), entries.next()) else {
+            return Err(vec![ConfigError::InvalidValue {
+                field: "assignment",
+                reason: "must contain exactly one key".to_string(),
+            }]);
+        };
+
+        if PROTECTED_FIELDS.contains(&key.as_str()) {
+            return Err(vec![ConfigError::InvalidValue {
+                field: "assignment",
+                reason: format!("{key} can not be changed remotely"),
+            }]);
+        }
+
+        let mut table = toml::Table::try_from(&*self).expect("Configuration is always a table");
+        table.insert(key.clone(), value.clone());
+        let changed = Self::from_toml(&table.to_string())?;
+
+        if let Some(path) = &self.path {
+            persist(path, &key, &value).map_err(|e| vec![e.into()])?;
+        }
+
+        let restart = RESTART_FIELDS.contains(&key.as_str());
+        if !restart {
+            *self = Self { path: self.path.take(), ..changed };
+        }
+        Ok(restart)
+    }
+
+    /// Parses and validates a configuration, returning every problem that was found
+    pub fn from_toml(s: &str) -> Result<Self, Vec<ConfigError>> {
+        let table: toml::Table = s.parse().map_err(|e| vec![ConfigError::Parse(e)])?;
+
+        let (known, unknown): (toml::Table, toml::Table) =
+            table.into_iter().partition(|(key, _)| FIELDS.contains(&key.as_str()));
+        let mut errors: Vec<ConfigError> =
+            unknown.into_iter().map(|(key, _)| ConfigError::UnknownField(key)).collect();
+
+        match toml::Value::Table(known).try_into::<Configuration>() {
+            Ok(config) => {
+                errors.extend(config.validate());
+                if errors.is_empty() {
+                    return Ok(config);
+                }
+            }
+            Err(e) => errors.push(e.into()),
+        }
+
+        Err(errors)
+    }
+
+    /// Checks the values for problems that the type system does not catch
+    #[must_use]
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let mut invalid = |field, reason: &str| {
+            errors.push(ConfigError::InvalidValue { field, reason: reason.to_string() });
+        };
+
+        if self.uart.is_empty() {
+            invalid("uart", "must not be empty");
+        }
+        if self.baudrate == 0 {
+            invalid("baudrate", "must not be zero");
+        }
+        if self.heartbeat_pin > MAXIMUM_PIN {
+            invalid("heartbeat_pin", &format!("must be at most {MAXIMUM_PIN}"));
+        }
+        if self.update_pin > MAXIMUM_PIN {
+            invalid("update_pin", &format!("must be at most {MAXIMUM_PIN}"));
+        }
+        if self.heartbeat_pin == self.update_pin {
+            invalid("update_pin", "must differ from heartbeat_pin");
+        }
+        if !(1..=MAXIMUM_HEARTBEAT_FREQ).contains(&self.heartbeat_freq) {
+            invalid("heartbeat_freq", &format!("must be between 1 and {MAXIMUM_HEARTBEAT_FREQ}"));
+        }
+        for (field, socket) in [("socket", &self.socket), ("api_socket", &self.api_socket)] {
+            if socket.is_empty() || socket.len() > MAXIMUM_SOCKET_PATH_LENGTH {
+                invalid(field, &format!("must be 1 to {MAXIMUM_SOCKET_PATH_LENGTH} bytes long"));
+            } else if !Path::new(socket)
+                .parent()
+                .is_some_and(|p| p.as_os_str().is_empty() || p.is_dir())
+            {
+                invalid(field, "parent directory does not exist");
+            }
+        }
+        if self.socket == self.api_socket {
+            invalid("api_socket", "must differ from socket");
+        }
+        for (field, path) in [
+            ("log_path", &self.log_path),
+            ("archives_path", &self.archives_path),
+            ("data_path", &self.data_path),
+            ("events_path", &self.events_path),
+            ("fault_log_path", &self.fault_log_path),
+            ("statistics_path", &self.statistics_path),
+            ("gpio_chip", &self.gpio_chip),
+            ("store_path", &self.store_path),
+        ] {
+            if path.as_os_str().is_empty() {
+                invalid(field, "must not be empty");
+            }
+        }
+        for (field, value) in [
+            ("log_max_size", self.log_max_size),
+            ("maximum_file_size", self.maximum_file_size as u64),
+            ("command_timeout_ms", self.command_timeout_ms),
+            ("event_send_tries", self.event_send_tries.into()),
+            ("terminate_timeout_ms", self.terminate_timeout_ms),
+            ("update_health_window_s", self.update_health_window_s),
+            ("watchdog_timeout_s", self.watchdog_timeout_s),
+            ("store_quota", self.store_quota),
+            ("store_max_keys", self.store_max_keys as u64),
+        ] {
+            if value == 0 {
+                invalid(field, "must not be zero");
+            }
+        }
+        if self.python.is_empty() {
+            invalid("python", "must not be empty");
+        }
+        if self.log_level.parse::<LevelFilter>().is_err() {
+            invalid("log_level", "must be off, error, warn, info, debug or trace");
+        }
+        for directive in &self.log_filters {
+            if !directive.contains('=')
+                || LogFilter::new(LevelFilter::Info).apply(directive).is_err()
+            {
+                invalid("log_filters", &format!("{directive:?} is not of the form module=level"));
+            }
+        }
+        for key in &self.trusted_keys {
+            if parse_verifying_key(key).is_none() {
+                invalid(
+                    "trusted_keys",
+                    &format!("{key:?} is not a hex encoded Ed25519 public key"),
+                );
+            }
+        }
+
+        errors
+    }
+
+    /// Combines `log_level` and `log_filters`
+    pub fn log_filter(&self) -> Result<LogFilter, ParseLevelError> {
+        let mut filter = LogFilter::new(LevelFilter::Info);
+        for directive in std::iter::once(&self.log_level).chain(&self.log_filters) {
+            filter.apply(directive)?;
+        }
+        Ok(filter)
+    }
+
+    /// The directory a student program is stored in
+    #[must_use]
+    pub fn program_path(&self, program_id: u16) -> PathBuf {
+        self.archives_path.join(program_id.to_string())
+    }
+
+    /// A file in the data directory, e.g. a result
+    #[must_use]
+    pub fn data_file(&self, name: impl Display) -> PathBuf {
+        self.data_path.join(name.to_string())
+    }
+
+    #[must_use]
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_millis(self.command_timeout_ms)
+    }
+
+    #[must_use]
+    pub fn terminate_timeout(&self) -> Duration {
+        Duration::from_millis(self.terminate_timeout_ms)
+    }
+
+    #[must_use]
+    pub fn update_health_window(&self) -> Duration {
+        Duration::from_secs(self.update_health_window_s)
+    }
+
+    #[must_use]
+    pub fn watchdog_timeout(&self) -> Duration {
+        Duration::from_secs(self.watchdog_timeout_s)
+    }
+
+    /// The keys archives and updates have to be signed with. Signatures are not checked if this is
+    /// empty.
+    #[must_use]
+    pub fn trusted_keys(&self) -> Vec<VerifyingKey> {
+        self.trusted_keys.iter().filter_map(|key| parse_verifying_key(key)).collect()
+    }
+}
+
+fn parse_verifying_key(hex: &str) -> Option<VerifyingKey> {
+    if hex.len() != 2 * ed25519_dalek::PUBLIC_KEY_LENGTH || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0; ed25519_dalek::PUBLIC_KEY_LENGTH];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Changes a single key in the given file, keeping all other keys and comments. The file is
+/// replaced atomically, so it is never left half-written.
+fn persist(path: &Path, key: &str, value: &toml::Value) -> std::io::Result<()> {
+    let invalid_data = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+
+    let mut document = match std::fs::read_to_string(path) {
+        Ok(s) => s.parse::<toml_edit::DocumentMut>().map_err(invalid_data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml_edit::DocumentMut::new(),
+        Err(e) => return Err(e),
+    };
+    let value = value.to_string().parse::<toml_edit::Value>().map_err(invalid_data)?;
+    match document.get_mut(key).and_then(toml_edit::Item::as_value_mut) {
+        // keep the comment that might follow the value
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = value;
+            *old.decor_mut() = decor;
+        }
+        None => document[key] = toml_edit::value(value),
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(document.to_string().as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+impl Display for Configuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = toml::to_string(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", s.lines().collect::<Vec<_>>().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_match_struct() {
+        let config = toml::Value::try_from(Configuration::default()).unwrap();
+        let keys: Vec<_> = config.as_table().unwrap().keys().map(String::as_str).collect();
+
+        assert_eq!(keys.len(), FIELDS.len());
+        assert!(keys.iter().all(|k| FIELDS.contains(k)));
+    }
+
+    #[test]
+    fn missing_fields_are_defaulted() {
+        let config = Configuration::from_toml("uart = \"/dev/ttyS0\"").unwrap();
+        assert_eq!(config, Configuration { uart: "/dev/ttyS0".to_string(), ..Default::default() });
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let errors = Configuration::from_toml(
+            "log_dir = \"log\"
+            foo = 1
+            heartbeat_freq = 0
+            update_pin = 60
+            socket = \"/does/not/exist/socket\"
+            log_filters = [\"communication=loud\"]",
+        )
+        .unwrap_err();
+
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 6, "{errors:?}");
+        assert!(errors.contains(&"Unknown field \"log_dir\"".to_string()));
+        assert!(errors.contains(&"Unknown field \"foo\"".to_string()));
+        assert!(errors.iter().any(|e| e.starts_with("Invalid value for heartbeat_freq")));
+        assert!(errors.iter().any(|e| e.starts_with("Invalid value for update_pin")));
+        assert!(errors.iter().any(|e| e.starts_with("Invalid value for socket")));
+        assert!(errors.iter().any(|e| e.starts_with("Invalid value for log_filters")));
+    }
+
+    #[test]
+    fn invalid_keys_take_their_default() {
+        let (config, errors) = Configuration::from_toml_lenient(
+            "heartbeat_freq = 20
+            update_pin = 60
+            baudrate = \"fast\"
+            foo = 1",
+        );
+
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert_eq!(config, Configuration { heartbeat_freq: 20, ..Default::default() });
+
+        let (config, errors) = Configuration::from_toml_lenient("not toml {");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(config, Configuration::default());
+    }
+
+    #[test]
+    fn wrong_type_is_reported() {
+        let errors = Configuration::from_toml("baudrate = \"fast\"\nbar = 2").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], ConfigError::UnknownField(_)));
+        assert!(matches!(errors[1], ConfigError::Parse(_)));
+    }
+
+    #[test]
+    fn default_is_valid() {
+        assert!(Configuration::default().validate().is_empty());
+    }
+
+    #[test]
+    fn shipped_config_is_default() {
+        let config = Configuration::load("config.toml").unwrap();
+        assert_eq!(
+            config,
+            Configuration { path: Some("config.toml".into()), ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn single_key_is_returned() {
+        let config = Configuration::default();
+        assert_eq!(config.get("heartbeat_freq").unwrap(), "heartbeat_freq = 10");
+        assert_eq!(config.get("python").unwrap(), "python = \"python\"");
+        assert!(config.get("path").is_none());
+    }
+
+    #[test]
+    fn changes_are_validated_and_persisted() {
+        let path = "__config_persisted";
+        std::fs::write(path, "heartbeat_freq = 10 # Hz\n# a comment\nevent_send_tries = 5\n")
+            .unwrap();
+        let mut config = Configuration::load(path).unwrap();
+
+        assert!(!config.set("event_send_tries = 3").unwrap());
+        assert_eq!(config.event_send_tries, 3);
+
+        assert!(config.set("heartbeat_freq = 20").unwrap());
+        assert_eq!(config.heartbeat_freq, 10);
+
+        assert_eq!(config.set("heartbeat_freq = 0").unwrap_err().len(), 1);
+        assert_eq!(config.set("unknown = 1").unwrap_err().len(), 1);
+        assert_eq!(config.set("command_timeout_ms = 5\nlog_files = 2").unwrap_err().len(), 1);
+        assert_eq!(config.set("trusted_keys = []").unwrap_err().len(), 1);
+        assert_eq!(config.set("python = \"/bin/sh\"").unwrap_err().len(), 1);
+        assert_eq!(config.set("update_pin_file = \"/etc/passwd\"").unwrap_err().len(), 1);
+        assert_eq!(config.set("data_path = \"/\"").unwrap_err().len(), 1);
+
+        assert!(!config.set("command_timeout_ms = 5").unwrap());
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "heartbeat_freq = 20 # Hz\n# a comment\nevent_send_tries = 3\ncommand_timeout_ms = 5\n"
+        );
+        let reloaded = Configuration::load(path).unwrap();
+        assert_eq!(reloaded.heartbeat_freq, 20);
+        assert_eq!(reloaded.command_timeout_ms, 5);
+        assert_eq!(reloaded.python, "python");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn trusted_keys_are_parsed() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]).verifying_key();
+        let hex = key.as_bytes().map(|b| format!("{b:02x}")).concat();
+        let config = Configuration { trusted_keys: vec![hex.clone()], ..Default::default() };
+        assert!(config.validate().is_empty());
+        assert_eq!(config.trusted_keys(), vec![key]);
+
+        for invalid in [&hex[1..], "zz", &hex.replace(|c: char| c.is_ascii_digit(), "g")] {
+            let config = Configuration { trusted_keys: vec![invalid.into()], ..Default::default() };
+            assert_eq!(config.validate().len(), 1, "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    command::{
+        start_program, terminate_student_program, CommandError, EventType, RetryEvent,
+        SyncExecutionContext, BUILD_HASH,
+    },
+    communication::SharedComStatistics,
+    logging,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A request on the control socket. Each request is a single line of JSON, with the kind of request
+/// in the field `request`, e.g. `{"request": "event", "event": "dosimeter/on"}`. Lines that are not
+/// JSON, like `dosimeter/on`, are events without payload as sent by producers of earlier versions.
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum Request {
+    /// Queues an event for the COBC, the name and payload are given by one of the `EVENT_TYPES`,
+    /// e.g. `{"request": "event", "event": "dosimeter/on", "payload": {}}`. The payload holds the
+    /// fields of the event type and may be omitted if it has none.
+    Event {
+        event: String,
+        #[serde(default)]
+        payload: Map<String, Value>,
+    },
+    /// The events that wait to be sent to the COBC
+    Events,
+    /// The student program that is currently running, `null` if there is none
+    Program,
+    /// The communication statistics of the current session and the lifetime of the EDU
+    Statistics,
+    /// Comma separated log filter directives, see `LogFilter::apply`
+    LogFilter { directives: String },
+    /// The build, the running program and the number of queued events
+    Status,
+    /// The ids of all stored programs
+    Programs,
+    /// Starts a program like Execute Program, the timeout is given in seconds
+    Execute { program_id: u16, timestamp: u32, timeout: u16 },
+    /// Stops the running program like Stop Program
+    Stop,
+}
+
+/// The answer to a request, a single line of JSON of the form `{"ok": value}` or
+/// `{"error": "reason"}`
+#[derive(serde::Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(String),
+}
+
+/// Limits how many events of the same kind are accepted on the socket, so that a noisy process
+/// can not flood the COBC
+#[derive(Default)]
+pub struct RateLimiter {
+    accepted: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    const WINDOW: Duration = Duration::from_mins(1);
+
+    /// Records an event of the given kind, unless `limit` of them were already accepted within the
+    /// last minute. A limit of 0 accepts every event.
+    pub fn accept(&mut self, kind: &str, limit: u32, now: Instant) -> bool {
+        let accepted = self.accepted.entry(kind.to_string()).or_default();
+        while accepted.front().is_some_and(|t| now.duration_since(*t) >= Self::WINDOW) {
+            accepted.pop_front();
+        }
+
+        if limit != 0 && accepted.len() >= limit as usize {
+            return false;
+        }
+        accepted.push_back(now);
+        true
+    }
+}
+
+/// Answers a single line received on the control socket
+pub fn respond(
+    line: &str,
+    exec: &SyncExecutionContext,
+    statistics: &SharedComStatistics,
+    limiter: &Mutex<RateLimiter>,
+) -> String {
+    let request = if line.starts_with('{') {
+        serde_json::from_str(line)
+    } else {
+        Ok(Request::Event { event: line.to_string(), payload: Map::new() })
+    };
+    let response = match request {
+        Ok(request) => handle_request(request, exec, statistics, limiter),
+        Err(e) => Response::Error(format!("invalid request: {e}")),
+    };
+    serde_json::to_string(&response).unwrap()
+}
+
+fn handle_request(
+    request: Request,
+    exec: &SyncExecutionContext,
+    statistics: &SharedComStatistics,
+    limiter: &Mutex<RateLimiter>,
+) -> Response {
+    match request {
+        Request::Event { event: name, payload } => {
+            let Some(event_type) = EventType::by_name(&name) else {
+                return Response::Error(format!("unknown event {name:?}"));
+            };
+            let event = match event_type.event(&payload) {
+                Ok(event) => event,
+                Err(e) => return Response::Error(format!("invalid payload for {name}: {e}")),
+            };
+            let mut context = exec.lock().unwrap();
+            let limit = context.config.socket_event_rate_limit;
+            if !limiter.lock().unwrap().accept(&name, limit, Instant::now()) {
+                log::warn!("Dropped {event} from socket, more than {limit} per minute");
+                return Response::Error(format!("more than {limit} {name} events per minute"));
+            }
+
+            log::info!("Received on socket: {event}");
+            let event = RetryEvent::new(event, context.config.event_send_tries);
+            if !context.config.coalesce_socket_events {
+                return match context.events.push(event) {
+                    Ok(()) => Response::Ok(Value::Null),
+                    Err(e) => Response::Error(format!("could not queue event: {e}")),
+                };
+            }
+            match context.events.push_coalesced(event) {
+                Ok(replaced) => {
+                    if replaced {
+                        log::debug!("Replaced the last queued event with {}", event.event);
+                    }
+                    Response::Ok(Value::Null)
+                }
+                Err(e) => Response::Error(format!("could not queue event: {e}")),
+            }
+        }
+        Request::Events => {
+            let context = exec.lock().unwrap();
+            let events = context.events.as_ref().iter();
+            Response::Ok(json!(events
+                .map(|e| json!({ "event": e.event.to_string(), "retries": e.retries }))
+                .collect::<Vec<_>>()))
+        }
+        Request::Program => Response::Ok(running_program(exec)),
+        Request::Statistics => {
+            let statistics = statistics.lock().unwrap();
+            Response::Ok(json!({ "session": statistics.session, "lifetime": statistics.lifetime }))
+        }
+        Request::LogFilter { directives } => match logging::apply_directives(&directives) {
+            Ok(filter) => {
+                log::info!("Log filter changed to {filter}");
+                Response::Ok(json!(filter.to_string()))
+            }
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::Status => {
+            let events = exec.lock().unwrap().events.as_ref().len();
+            Response::Ok(json!({
+                "build": BUILD_HASH,
+                "program": running_program(exec),
+                "events": events,
+            }))
+        }
+        Request::Programs => {
+            let archives_path = exec.lock().unwrap().config.archives_path.clone();
+            match stored_programs(&archives_path) {
+                Ok(programs) => Response::Ok(json!(programs)),
+                Err(e) => Response::Error(format!("could not list programs: {e}")),
+            }
+        }
+        Request::Execute { program_id, timestamp, timeout } => {
+            let timeout = Duration::from_secs(timeout.into());
+            match start_program(&mut exec.clone(), program_id, timestamp, timeout) {
+                Ok(()) => Response::Ok(Value::Null),
+                Err((_, e)) => Response::Error(describe(&e)),
+            }
+        }
+        Request::Stop => match terminate_student_program(&mut exec.clone()) {
+            Ok(()) => Response::Ok(Value::Null),
+            Err((_, e)) => Response::Error(describe(&e)),
+        },
+    }
+}
+
+/// The cause of the error without the backtrace, which `CommandError` is displayed with
+fn describe(error: &CommandError) -> String {
+    let (CommandError::NonRecoverable(e)
+    | CommandError::External(e)
+    | CommandError::ProtocolViolation(e)) = error;
+    format!("{e:#}")
+}
+
+fn running_program(exec: &SyncExecutionContext) -> Value {
+    let context = exec.lock().unwrap();
+    context
+        .api_session
+        .as_ref()
+        .map_or(Value::Null, |s| json!({ "program_id": s.program_id, "timestamp": s.timestamp }))
+}
+
+/// The ids of the program directories in `archives_path`, in ascending order
+fn stored_programs(archives_path: &std::path::Path) -> std::io::Result<Vec<u16>> {
+    let mut programs = Vec::new();
+    for entry in std::fs::read_dir(archives_path)? {
+        let entry = entry?;
+        if let Some(id) = entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            if entry.path().join("main.py").exists() {
+                programs.push(id);
+            }
+        }
+    }
+    programs.sort_unstable();
+    Ok(programs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::{ExecutionContext, MemoryNotifier},
+        communication::ComStatistics,
+        config::Configuration,
+        student_api::ApiSession,
+    };
+
+    #[test]
+    fn requests_are_parsed() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"request": "event", "event": "dosimeter/on"}"#)
+                .unwrap(),
+            Request::Event { event: "dosimeter/on".into(), payload: Map::new() }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"request": "events"}"#).unwrap(),
+            Request::Events
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"request": "unknown"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"request": "event"}"#).is_err());
+    }
+
+    #[test]
+    fn requests_are_answered() {
+        let _ = std::fs::remove_file("__control_events");
+        let config = Configuration {
+            events_path: "__control_events".into(),
+            fault_log_path: "__control_faults".into(),
+            ..Default::default()
+        };
+        let exec =
+            ExecutionContext::with_notifier(config, Box::new(MemoryNotifier::default())).unwrap();
+        let statistics = ComStatistics::default().shared();
+        let limiter = Mutex::default();
+        let respond = |line| respond(line, &exec, &statistics, &limiter);
+
+        assert_eq!(respond(r#"{"request": "event", "event": "dosimeter/on"}"#), r#"{"ok":null}"#);
+        assert_eq!(
+            respond(r#"{"request": "event", "event": "camera/on"}"#),
+            r#"{"error":"unknown event \"camera/on\""}"#
+        );
+        assert_eq!(
+            respond(r#"{"request": "events"}"#),
+            r#"{"ok":[{"event":"dosimeter/on","retries":5}]}"#
+        );
+        assert_eq!(respond(r#"{"request": "program"}"#), r#"{"ok":null}"#);
+        exec.lock().unwrap().api_session = Some(ApiSession::new(3, 10).unwrap());
+        assert_eq!(
+            respond(r#"{"request": "program"}"#),
+            r#"{"ok":{"program_id":3,"timestamp":10}}"#
+        );
+        assert!(respond(r#"{"request": "statistics"}"#).starts_with(r#"{"ok":{"lifetime":"#));
+        assert_eq!(respond("dosimeter/on"), r#"{"ok":null}"#);
+        assert_eq!(respond("camera/on"), r#"{"error":"unknown event \"camera/on\""}"#);
+        assert!(respond("{request}").starts_with(r#"{"error":"invalid request"#));
+        assert!(respond(r#"{"request": "status"}"#).contains(r#""events":1"#));
+        assert_eq!(
+            respond(r#"{"request": "execute", "program_id": 999, "timestamp": 0, "timeout": 1}"#),
+            r#"{"error":"Could not find matching program"}"#
+        );
+        assert_eq!(respond(r#"{"request": "stop"}"#), r#"{"ok":null}"#);
+
+        let _ = std::fs::remove_file("__control_events");
+        let _ = std::fs::remove_file("__control_faults");
+    }
+
+    #[test]
+    fn events_are_coalesced_and_limited() {
+        let _ = std::fs::remove_file("__control_limited_events");
+        let config = Configuration {
+            events_path: "__control_limited_events".into(),
+            fault_log_path: "__control_limited_faults".into(),
+            socket_event_rate_limit: 3,
+            ..Default::default()
+        };
+        let exec =
+            ExecutionContext::with_notifier(config, Box::new(MemoryNotifier::default())).unwrap();
+        let statistics = ComStatistics::default().shared();
+        let limiter = Mutex::default();
+        let respond = |line| respond(line, &exec, &statistics, &limiter);
+
+        for _ in 0..3 {
+            assert_eq!(
+                respond(r#"{"request": "event", "event": "dosimeter/on"}"#),
+                r#"{"ok":null}"#
+            );
+            assert_eq!(
+                respond(r#"{"request": "event", "event": "dosimeter/off"}"#),
+                r#"{"ok":null}"#
+            );
+        }
+        assert_eq!(
+            respond(r#"{"request": "event", "event": "dosimeter/on"}"#),
+            r#"{"error":"more than 3 dosimeter/on events per minute"}"#
+        );
+        let context = exec.lock().unwrap();
+        assert_eq!(context.events.as_ref().len(), 1);
+        assert_eq!(context.events.as_ref()[0].event.to_string(), "dosimeter/off");
+        drop(context);
+
+        let _ = std::fs::remove_file("__control_limited_events");
+        let _ = std::fs::remove_file("__control_limited_faults");
+    }
+
+    #[test]
+    fn rate_limit_window_slides() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert!(limiter.accept("a", 2, start));
+        assert!(limiter.accept("a", 2, start + Duration::from_secs(30)));
+        assert!(!limiter.accept("a", 2, start + Duration::from_secs(59)));
+        assert!(limiter.accept("b", 2, start + Duration::from_secs(59)));
+        assert!(limiter.accept("a", 2, start + Duration::from_mins(1)));
+        assert!(!limiter.accept("a", 2, start + Duration::from_secs(61)));
+        assert!((0..100).all(|_| limiter.accept("c", 0, start)));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::Configuration;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// The ways GPIO pins can be accessed, selected by `gpio_backend` in the configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpioBackendKind {
+    /// The memory mapped registers of the Raspberry Pi
+    Rppal,
+    /// The Linux GPIO character device given by `gpio_chip`
+    Cdev,
+    /// Pins only exist in memory, for testing without hardware
+    Simulated,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GpioError {
+    #[error(transparent)]
+    Rppal(#[from] rppal::gpio::Error),
+    #[error(transparent)]
+    Cdev(#[from] gpio_cdev::Error),
+}
+
+/// Provides access to the GPIO pins
+pub trait GpioBackend: Send + Sync {
+    fn output(&self, pin: u8) -> Result<Box<dyn OutputPin>, GpioError>;
+}
+
+pub trait OutputPin: Send {
+    fn set(&mut self, high: bool);
+}
+
+/// Opens the backend selected in the configuration
+pub fn open(config: &Configuration) -> Result<Arc<dyn GpioBackend>, GpioError> {
+    Ok(match config.gpio_backend {
+        GpioBackendKind::Rppal => Arc::new(RppalBackend(rppal::gpio::Gpio::new()?)),
+        GpioBackendKind::Cdev => Arc::new(CdevBackend { chip: config.gpio_chip.clone() }),
+        GpioBackendKind::Simulated => Arc::new(SimulatedBackend::default()),
+    })
+}
+
+pub struct RppalBackend(rppal::gpio::Gpio);
+
+impl GpioBackend for RppalBackend {
+    fn output(&self, pin: u8) -> Result<Box<dyn OutputPin>, GpioError> {
+        let mut pin = self.0.get(pin)?.into_output();
+        // the COBC must not see a spurious change when the scheduler restarts
+        pin.set_reset_on_drop(false);
+        Ok(Box::new(pin))
+    }
+}
+
+impl OutputPin for rppal::gpio::OutputPin {
+    fn set(&mut self, high: bool) {
+        if high {
+            self.set_high();
+        } else {
+            self.set_low();
+        }
+    }
+}
+
+pub struct CdevBackend {
+    chip: PathBuf,
+}
+
+impl GpioBackend for CdevBackend {
+    fn output(&self, pin: u8) -> Result<Box<dyn OutputPin>, GpioError> {
+        let mut chip = gpio_cdev::Chip::new(&self.chip)?;
+        let handle = chip.get_line(pin.into())?.request(
+            gpio_cdev::LineRequestFlags::OUTPUT,
+            0,
+            "STS1_EDU_Scheduler",
+        )?;
+        Ok(Box::new(handle))
+    }
+}
+
+impl OutputPin for gpio_cdev::LineHandle {
+    fn set(&mut self, high: bool) {
+        if let Err(e) = self.set_value(high.into()) {
+            log::error!("Could not set GPIO line {}: {e}", self.line().offset());
+        }
+    }
+}
+
+/// The level changes of each pin, together with the time they happened
+pub type Transitions = Arc<Mutex<HashMap<u8, Vec<(Instant, bool)>>>>;
+
+/// Records every level change of its pins
+#[derive(Clone, Default)]
+pub struct SimulatedBackend {
+    pub pins: Transitions,
+}
+
+impl GpioBackend for SimulatedBackend {
+    fn output(&self, pin: u8) -> Result<Box<dyn OutputPin>, GpioError> {
+        self.pins.lock().unwrap().entry(pin).or_default();
+        Ok(Box::new(SimulatedPin { pin, pins: self.pins.clone() }))
+    }
+}
+
+struct SimulatedPin {
+    pin: u8,
+    pins: Transitions,
+}
+
+impl OutputPin for SimulatedPin {
+    fn set(&mut self, high: bool) {
+        let mut pins = self.pins.lock().unwrap();
+        let transitions = pins.entry(self.pin).or_default();
+        if transitions.last().map(|(_, level)| *level) != Some(high) {
+            transitions.push((Instant::now(), high));
+        }
+    }
+}
@@ -1,3 +1,11 @@
 #![allow(non_snake_case)]
 pub mod command;
 pub mod communication;
+pub mod config;
+pub mod control;
+pub mod gpio;
+pub mod logging;
+pub mod student_api;
+pub mod systemd;
+pub mod update;
+pub mod watchdog;
@@ -0,0 +1,320 @@
+use log::{LevelFilter, Log, Metadata, ParseLevelError, Record};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, RwLock},
+};
+
+/// An excerpt is not extended beyond this size, so a chatty program can not exhaust the memory
+const MAXIMUM_EXCERPT_SIZE: usize = 100_000;
+
+/// Collects everything that is logged while it is `Some`
+static EXCERPT: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+/// Decides which records are passed on by the [`FilteredLogger`]
+static FILTER: RwLock<LogFilter> = RwLock::new(LogFilter::new(LevelFilter::Info));
+
+/// The maximum level that is logged, per module. Modules are given by their path without the crate
+/// name, e.g. `communication` or `command::execute_program`, and also apply to their submodules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    /// The level for all modules that have no filter of their own
+    pub default: LevelFilter,
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    #[must_use]
+    pub const fn new(default: LevelFilter) -> Self {
+        Self { default, modules: Vec::new() }
+    }
+
+    /// Sets the level of the given module, or the default level if it is `None`
+    pub fn set(&mut self, module: Option<&str>, level: LevelFilter) {
+        let Some(module) = module else {
+            self.default = level;
+            return;
+        };
+
+        match self.modules.iter_mut().find(|(m, _)| m == module) {
+            Some((_, l)) => *l = level,
+            None => self.modules.push((module.to_string(), level)),
+        }
+    }
+
+    /// Applies a directive of the form `level` or `module=level`
+    pub fn apply(&mut self, directive: &str) -> Result<(), ParseLevelError> {
+        match directive.split_once('=') {
+            Some((module, level)) => self.set(Some(module.trim()), level.trim().parse()?),
+            None => self.set(None, directive.trim().parse()?),
+        }
+        Ok(())
+    }
+
+    /// Returns the level of the most specific filter matching `target`
+    #[must_use]
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        let path = target.split_once("::").map_or(target, |(_, path)| path);
+        let matches = |module: &str| {
+            [target, path].iter().any(|t| {
+                t.strip_prefix(module).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+        };
+
+        self.modules
+            .iter()
+            .filter(|(module, _)| matches(module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, l)| *l).fold(self.default, Ord::max)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = ParseLevelError;
+
+    /// Parses a comma separated list of directives, see [`LogFilter::apply`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = LogFilter::new(LevelFilter::Info);
+        for directive in s.split(',').filter(|d| !d.trim().is_empty()) {
+            filter.apply(directive)?;
+        }
+        Ok(filter)
+    }
+}
+
+impl Display for LogFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{module}={}", level.as_str().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the active filter
+pub fn set_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+    *FILTER.write().unwrap() = filter;
+}
+
+/// Applies the comma separated directives to the active filter and returns the result. If any of
+/// them is invalid, the active filter is left untouched.
+pub fn apply_directives(directives: &str) -> Result<LogFilter, ParseLevelError> {
+    let mut filter = FILTER.read().unwrap().clone();
+    for directive in directives.split(',').filter(|d| !d.trim().is_empty()) {
+        filter.apply(directive)?;
+    }
+    set_filter(filter.clone());
+    Ok(filter)
+}
+
+/// Wraps another logger and only passes on records that are enabled by the active [`LogFilter`]
+pub struct FilteredLogger(pub Box<dyn Log>);
+
+impl FilteredLogger {
+    /// Installs the logger with the given filter as the global logger
+    pub fn init(logger: Box<dyn Log>, filter: LogFilter) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(FilteredLogger(logger)))?;
+        set_filter(filter);
+        Ok(())
+    }
+}
+
+impl Log for FilteredLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTER.read().unwrap().level_for(metadata.target())
+            && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Starts collecting all lines that are logged from now on, discarding a previous excerpt
+pub fn start_excerpt() {
+    *EXCERPT.lock().unwrap() = Some(Vec::new());
+}
+
+/// Stops collecting log lines and returns the ones collected since `start_excerpt`
+#[must_use]
+pub fn take_excerpt() -> Vec<u8> {
+    EXCERPT.lock().unwrap().take().unwrap_or_default()
+}
+
+/// Returns the path of the log with the given index, where 0 is the current log and higher indices
+/// are older ones. Rotated logs are stored next to the current one as `log.1`, `log.2`, ...
+#[must_use]
+pub fn rotated_log_path(base: impl AsRef<Path>, index: u8) -> PathBuf {
+    let base = base.as_ref();
+    if index == 0 {
+        base.into()
+    } else {
+        let mut path = base.as_os_str().to_owned();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+/// A log file that is rotated into numbered files once it grows beyond `max_size`. Rotation only
+/// happens at the start of a line, so a single line is never split across two files.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    max_size: u64,
+    max_files: u8,
+    file: File,
+    size: u64,
+    at_line_start: bool,
+}
+
+impl RotatingFileWriter {
+    /// Opens the log file at `path` for appending
+    pub fn open(path: impl Into<PathBuf>, max_size: u64, max_files: u8) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = Self::open_file(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self { path, max_size, max_files, file, size, at_line_start: true })
+    }
+
+    fn open_file(path: &Path) -> std::io::Result<File> {
+        std::fs::OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Shifts every rotated log by one index, deleting the oldest, and starts a new log file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = rotated_log_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(from, rotated_log_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_log_path(&self.path, 1))?;
+        }
+
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.at_line_start && self.size >= self.max_size {
+            self.rotate()?;
+        }
+
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        if n > 0 {
+            self.at_line_start = buf[n - 1] == b'\n';
+        }
+
+        if let Some(excerpt) = EXCERPT.lock().unwrap().as_mut() {
+            let remaining = MAXIMUM_EXCERPT_SIZE.saturating_sub(excerpt.len());
+            excerpt.extend_from_slice(&buf[..n.min(remaining)]);
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remove_logs(path: &str) {
+        for index in 0..=4 {
+            let _ = std::fs::remove_file(rotated_log_path(path, index));
+        }
+    }
+
+    #[test]
+    fn log_is_rotated_at_line_start() {
+        let path = "__log_rotated";
+        remove_logs(path);
+
+        let mut writer = RotatingFileWriter::open(path, 10, 2).unwrap();
+        write!(writer, "first line").unwrap();
+        writeln!(writer, " continued").unwrap();
+        writeln!(writer, "second").unwrap();
+        writeln!(writer, "third").unwrap();
+        writeln!(writer, "fourth").unwrap();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "fourth\n");
+        assert_eq!(std::fs::read_to_string("__log_rotated.1").unwrap(), "second\nthird\n");
+        assert_eq!(std::fs::read_to_string("__log_rotated.2").unwrap(), "first line continued\n");
+
+        writeln!(writer, "fifth line").unwrap();
+        writeln!(writer, "sixth").unwrap();
+        assert_eq!(std::fs::read_to_string("__log_rotated.2").unwrap(), "second\nthird\n");
+        assert!(!Path::new("__log_rotated.3").exists());
+
+        remove_logs(path);
+    }
+
+    #[test]
+    fn most_specific_module_filter_is_used() {
+        let filter: LogFilter =
+            "warn,communication=debug,communication::cep=trace".parse().unwrap();
+
+        assert_eq!(filter.level_for("STS1_EDU_Scheduler::command"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("STS1_EDU_Scheduler::communication"), LevelFilter::Debug);
+        assert_eq!(
+            filter.level_for("STS1_EDU_Scheduler::communication::socket"),
+            LevelFilter::Debug
+        );
+        assert_eq!(filter.level_for("STS1_EDU_Scheduler::communication::cep"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("STS1_EDU_Scheduler::communications"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_is_displayed_as_directives() {
+        let mut filter: LogFilter = "communication=debug".parse().unwrap();
+        filter.apply("error").unwrap();
+        filter.apply("communication=off").unwrap();
+
+        assert_eq!(filter.to_string(), "error,communication=off");
+        assert!(filter.apply("communication=loud").is_err());
+    }
+
+    #[test]
+    fn excerpt_contains_lines_logged_in_between() {
+        let path = "__log_excerpt";
+        remove_logs(path);
+
+        let mut writer = RotatingFileWriter::open(path, 1_000_000, 4).unwrap();
+        writeln!(writer, "before").unwrap();
+        start_excerpt();
+        writeln!(writer, "during").unwrap();
+        assert_eq!(take_excerpt(), b"during\n");
+        writeln!(writer, "after").unwrap();
+
+        assert!(take_excerpt().is_empty());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "before\nduring\nafter\n");
+
+        remove_logs(path);
+    }
+}
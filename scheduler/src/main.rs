@@ -1,117 +1,246 @@
 #![allow(non_snake_case)]
-use crate::command::Event;
-use command::{ExecutionContext, RetryEvent};
-use communication::socket::UnixSocketParser;
-use core::time;
-use rppal::gpio::Gpio;
-use serialport::SerialPort;
+use communication::SharedComStatistics;
+use communication::{
+    socket::UnixSocketServer, ComStatistics, CommunicationHandle, SerialComHandle,
+};
+use config::Configuration;
+use logging::{FilteredLogger, LogFilter};
 use simplelog as sl;
 use std::{
-    io::ErrorKind,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread,
+    time::Duration,
 };
-use STS1_EDU_Scheduler::communication::CommunicationHandle;
+use systemd::SystemdNotifier;
+use update::{TrialState, Updater};
+use watchdog::{Heartbeat, Watchdog};
 
 mod command;
 mod communication;
-
-#[derive(serde::Deserialize)]
-struct Configuration {
-    uart: String,
-    baudrate: u32,
-    heartbeat_pin: u8,
-    update_pin: u8,
-    heartbeat_freq: u64,
-    socket: String,
-}
-
-impl Default for Configuration {
-    fn default() -> Self {
-        Self {
-            uart: "/dev/serial0".to_string(),
-            baudrate: 921_600,
-            heartbeat_pin: 34,
-            update_pin: 35,
-            heartbeat_freq: 10,
-            socket: "/tmp/scheduler_socket".to_string(),
-        }
-    }
-}
+mod config;
+mod control;
+mod gpio;
+mod logging;
+mod student_api;
+mod systemd;
+mod update;
+mod watchdog;
+
+/// How long the command loop waits for the COBC before checking in with the watchdog
+const CHECK_IN_INTERVAL: Duration = Duration::from_secs(1);
+/// How often `save_statistics_on_termination` checks wether the scheduler was asked to terminate
+const TERMINATION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Set by the handler of SIGTERM and SIGINT
+static TERMINATION_REQUESTED: AtomicBool = AtomicBool::new(false);
 
 fn main() -> ! {
-    let _ = sl::WriteLogger::init(
-        sl::LevelFilter::Info,
-        sl::Config::default(),
-        std::fs::OpenOptions::new().create(true).append(true).open("log").unwrap(),
+    // the log path is configurable, so problems with the configuration are only logged afterwards
+    let (config, config_errors) = match Configuration::load("./config.toml") {
+        Ok(config) => (config, Vec::new()),
+        Err(_) => Configuration::load_lenient("./config.toml"),
+    };
+    // an update that panics during the initialization below must still be rolled back
+    let trial = resume_update_trial(&config);
+
+    let _ = FilteredLogger::init(
+        sl::WriteLogger::new(
+            sl::LevelFilter::Trace,
+            sl::Config::default(),
+            logging::RotatingFileWriter::open(
+                &config.log_path,
+                config.log_max_size,
+                config.log_files,
+            )
+            .unwrap(),
+        ),
+        LogFilter::new(sl::LevelFilter::Info),
     );
 
-    let config: Configuration = if let Ok(s) = std::fs::read_to_string("./config.toml") {
-        toml::from_str(&s)
-            .map_err(|e| log::error!("Could not parse config {e:?}, using default"))
-            .unwrap_or_default()
-    } else {
-        log::error!("Could not open config.toml, using default");
-        Configuration::default()
-    };
+    for e in &config_errors {
+        log::error!("{e}");
+    }
+    if !config_errors.is_empty() {
+        log::error!("Invalid configuration, the keys above take their default value");
+    }
+    match &trial {
+        Ok(Some(_)) => log::info!("Update on trial for {:?}", config.update_health_window()),
+        Ok(None) => (),
+        Err(e) => log::error!("Could not resume update trial: {e}"),
+    }
+    match config.log_filter() {
+        Ok(filter) => logging::set_filter(filter),
+        Err(e) => log::error!("Invalid log filter: {e}"),
+    }
 
-    create_directory_if_not_exists("archives").unwrap();
-    create_directory_if_not_exists("data").unwrap();
+    create_directory_if_not_exists(&config.archives_path).unwrap();
+    create_directory_if_not_exists(&config.data_path).unwrap();
+    if let Err(e) = command::recover_stores(&config) {
+        log::error!("Could not recover the program stores: {e}");
+    }
 
-    log::info!("Scheduler started");
+    log::info!("Scheduler started, build {}", command::BUILD_HASH);
+    log::info!("Effective configuration: {config}");
 
     // construct a wrapper for UART communication
-    let mut com =
-        serialport::new(&config.uart, config.baudrate).open().expect("Could not open serial port");
-    com.set_timeout(<Box<dyn SerialPort> as CommunicationHandle>::UNLIMITED_TIMEOUT);
+    let statistics = ComStatistics::open(&config.statistics_path).shared();
+    let mut com = SerialComHandle::new(
+        serialport::new(&config.uart, config.baudrate).open().expect("Could not open serial port"),
+        statistics.clone(),
+    );
+    save_statistics_on_termination(statistics.clone());
+    com.set_timeout(SerialComHandle::UNLIMITED_TIMEOUT);
 
     // construct a wrapper for resources that are shared between different commands
-    let mut exec = command::ExecutionContext::new("events".to_string(), config.update_pin).unwrap();
+    let gpio = gpio::open(&config).unwrap_or_else(|e| {
+        log::error!("Could not open GPIO backend {:?}, simulating pins: {e}", config.gpio_backend);
+        Arc::new(gpio::SimulatedBackend::default())
+    });
+    let mut exec = command::ExecutionContext::new(config.clone(), gpio.as_ref()).unwrap();
+
+    let watchdog = exec.lock().unwrap().watchdog.clone();
+    com.set_watchdog(
+        watchdog.register("command", config.watchdog_timeout()),
+        config.watchdog_timeout(),
+    );
 
-    let socket_rx = communication::socket::UnixSocketParser::new(&config.socket).unwrap();
-    let socket_context = exec.clone();
-    std::thread::spawn(move || event_socket_loop(&socket_context, socket_rx));
+    let control_server = UnixSocketServer::bind(&config.socket).unwrap();
+    let control_context = exec.clone();
+    let control_watchdog = watchdog.clone();
+    let timeout = config.watchdog_timeout();
+    thread::spawn(move || {
+        let limiter = Mutex::default();
+        let handler =
+            move |line: &str| control::respond(line, &control_context, &statistics, &limiter);
+        control_server.serve(handler, &control_watchdog, "socket", timeout);
+    });
+
+    let api_server = UnixSocketServer::bind(&config.api_socket).unwrap();
+    let api_context = exec.clone();
+    let api_watchdog = watchdog.clone();
+    thread::spawn(move || {
+        let handler = move |line: &str| student_api::respond(line, &api_context);
+        api_server.serve(handler, &api_watchdog, "api", timeout);
+    });
+
+    start_systemd_notification(&watchdog);
+    if let Ok(Some(updater)) = trial {
+        supervise_update_trial(updater, &config, &watchdog, com.statistics().unwrap().clone());
+    }
 
     // start a thread that will update the heartbeat pin
-    thread::spawn(move || heartbeat_loop(config.heartbeat_pin, config.heartbeat_freq));
+    match gpio.output(config.heartbeat_pin) {
+        Ok(pin) => {
+            let mut heartbeat = Heartbeat::new(pin, config.heartbeat_freq);
+            thread::spawn(move || loop {
+                heartbeat.beat(&watchdog);
+            });
+        }
+        Err(e) => log::error!("Could not open heartbeat pin: {e}"),
+    }
 
     // main loop
     loop {
+        if matches!(com.wait_for_data(CHECK_IN_INTERVAL), Ok(false)) {
+            continue;
+        }
+
         command::handle_command(&mut com, &mut exec);
     }
 }
 
-fn heartbeat_loop(heartbeat_pin: u8, freq: u64) -> ! {
-    if cfg!(feature = "mock") {
-        std::thread::park();
+/// Must run before anything that could panic, so that an update which does not even get through
+/// the initialization is rolled back on its next start. An update that was restarted during its
+/// trial is rolled back here. Returns the updater if the running binary is on trial, see
+/// `supervise_update_trial`.
+fn resume_update_trial(config: &Configuration) -> std::io::Result<Option<Updater>> {
+    let updater = Updater::for_current_executable(config)?;
+    match updater.resume_trial()? {
+        TrialState::Confirmed => Ok(None),
+        TrialState::Running => Ok(Some(updater)),
+        TrialState::RolledBack => {
+            // the logger is not initialized yet
+            eprintln!("Update was restarted during its trial, restarting into previous binary");
+            Err(update::restart(updater.executable()))
+        }
     }
+}
 
-    let toogle_time = time::Duration::from_millis(1000 / freq / 2);
+/// Starts a thread that confirms the update on trial if the threads monitored by `watchdog` stay
+/// responsive, see `update::supervise_trial`. Otherwise it restarts into the previous binary.
+fn supervise_update_trial(
+    updater: Updater,
+    config: &Configuration,
+    watchdog: &Watchdog,
+    statistics: SharedComStatistics,
+) {
+    let window = config.update_health_window();
+    let watchdog = watchdog.clone();
+    thread::spawn(move || {
+        if !update::supervise_trial(&updater, window, &watchdog) {
+            save_statistics(&statistics);
+            let e = update::restart(updater.executable());
+            log::error!("Could not restart: {e}");
+        }
+    });
+}
 
-    let gpio = Gpio::new().unwrap();
-    let mut pin = gpio.get(heartbeat_pin).unwrap().into_output();
+/// The communication statistics are only saved periodically, see `ComStatistics::save_if_due`.
+/// This saves them once more when systemd stops the scheduler or it is interrupted.
+fn save_statistics_on_termination(statistics: SharedComStatistics) {
+    extern "C" fn request_termination(_: libc::c_int) {
+        TERMINATION_REQUESTED.store(true, Ordering::Relaxed);
+    }
 
-    loop {
-        pin.set_high();
-        thread::sleep(toogle_time);
-        pin.set_low();
-        thread::sleep(toogle_time);
+    let handler = request_termination as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in [libc::SIGTERM, libc::SIGINT] {
+        // SAFETY: the handler only stores into an atomic, which is async-signal-safe
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            log::error!("Could not install handler for signal {signal}");
+        }
     }
+
+    thread::spawn(move || {
+        while !TERMINATION_REQUESTED.load(Ordering::Relaxed) {
+            thread::sleep(TERMINATION_POLL_INTERVAL);
+        }
+        log::info!("Terminating");
+        save_statistics(&statistics);
+        log::logger().flush();
+        std::process::exit(0);
+    });
 }
 
-fn event_socket_loop(context: &Arc<Mutex<ExecutionContext>>, mut socket: UnixSocketParser) {
-    loop {
-        let s = socket.read_object::<Event>();
-        let event = match s {
-            Ok(e) => e,
-            Err(ref e) if e.kind() == ErrorKind::Other => break,
-            Err(_) => continue,
-        };
-
-        log::info!("Received on socket: {event:?}");
-        let mut context = context.lock().unwrap();
-        context.event_vec.push(RetryEvent::new(event)).unwrap();
-        context.configure_update_pin();
+fn save_statistics(statistics: &SharedComStatistics) {
+    if let Err(e) = statistics.lock().unwrap().save() {
+        log::error!("Could not persist communication statistics: {e}");
+    }
+}
+
+/// Tells systemd that the scheduler is ready and starts petting the systemd watchdog, if it is
+/// enabled. Does nothing if the scheduler was not started by systemd.
+fn start_systemd_notification(watchdog: &Watchdog) {
+    let notifier = match SystemdNotifier::from_env() {
+        Ok(Some(notifier)) => notifier,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Could not connect to systemd: {e}");
+            return;
+        }
+    };
+
+    let state = format!("READY=1\nSTATUS=Running build {}", command::BUILD_HASH);
+    if let Err(e) = notifier.notify(&state) {
+        log::warn!("Could not notify systemd: {e}");
+    }
+
+    if let Some(interval) = systemd::watchdog_interval() {
+        log::info!("Systemd watchdog enabled with {interval:?}");
+        let watchdog = watchdog.clone();
+        thread::spawn(move || systemd::watchdog_loop(&notifier, interval, &watchdog));
     }
 }
 
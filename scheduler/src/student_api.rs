@@ -0,0 +1,232 @@
+use crate::{
+    command::{CustomEvent, Event, RetryEvent, SyncExecutionContext, MAXIMUM_CUSTOM_PAYLOAD},
+    control::RateLimiter,
+};
+use std::{io::Read, str::FromStr, time::Instant};
+
+/// The environment variable that holds the path of the API socket for a running student program
+pub const SOCKET_ENV: &str = "EDU_API_SOCKET";
+/// The environment variable that holds the token a running student program authenticates with
+pub const TOKEN_ENV: &str = "EDU_API_TOKEN";
+/// In bytes, the token is hex encoded
+const TOKEN_LENGTH: usize = 16;
+
+/// The execution of a student program, which may use the API socket while it is running
+pub struct ApiSession {
+    pub program_id: u16,
+    pub timestamp: u32,
+    pub token: String,
+    /// Set once the program asked for its result to be packed, see `ApiAction::Package`
+    pub package_requested: bool,
+    /// Limits the custom events of this run like those on the control socket
+    limiter: RateLimiter,
+}
+
+impl ApiSession {
+    /// Creates a session with a random token
+    pub fn new(program_id: u16, timestamp: u32) -> std::io::Result<Self> {
+        let mut bytes = [0; TOKEN_LENGTH];
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+        let token = bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().concat();
+        Ok(Self {
+            program_id,
+            timestamp,
+            token,
+            package_requested: false,
+            limiter: RateLimiter::default(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ApiAction {
+    /// Free text, which is logged and thereby ends up in the result
+    Progress(String),
+    /// Queues an `Event::Custom` with the given payload for the COBC
+    Event(Vec<u8>),
+    /// Stops the program and packs its result right away, instead of waiting for it to exit
+    Package,
+}
+
+/// A line received on the API socket, of the form `{token} progress {text}`,
+/// `{token} event {hex encoded payload}` or `{token} package`
+#[derive(Debug, PartialEq, Eq)]
+struct ApiRequest {
+    token: String,
+    action: ApiAction,
+}
+
+impl FromStr for ApiRequest {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (token, rest) = s.split_once(' ').ok_or(())?;
+        let (action, argument) = rest.split_once(' ').unwrap_or((rest, ""));
+        let action = match action {
+            "progress" => ApiAction::Progress(argument.to_string()),
+            "event" => ApiAction::Event(parse_hex(argument).ok_or(())?),
+            "package" if argument.is_empty() => ApiAction::Package,
+            _ => return Err(()),
+        };
+
+        Ok(Self { token: token.to_string(), action })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum ApiError {
+    #[error("invalid request")]
+    InvalidRequest,
+    #[error("no program is running")]
+    NotRunning,
+    #[error("invalid token")]
+    InvalidToken,
+    #[error("payload is longer than {MAXIMUM_CUSTOM_PAYLOAD} bytes")]
+    PayloadTooLong,
+    #[error("more than {0} events per minute")]
+    RateLimited(u32),
+    #[error("could not queue event: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Answers a single line received on the API socket with either `ok` or `error: {reason}`
+pub fn respond(line: &str, exec: &SyncExecutionContext) -> String {
+    let result = line.parse().map_err(|()| ApiError::InvalidRequest);
+    match result.and_then(|request| handle_request(request, exec)) {
+        Ok(()) => "ok".to_string(),
+        Err(e) => {
+            log::warn!("Rejected API request: {e}");
+            format!("error: {e}")
+        }
+    }
+}
+
+fn handle_request(request: ApiRequest, exec: &SyncExecutionContext) -> Result<(), ApiError> {
+    let mut context = exec.lock().unwrap();
+    let limit = context.config.socket_event_rate_limit;
+    let session = context.api_session.as_mut().ok_or(ApiError::NotRunning)?;
+    if session.token != request.token {
+        return Err(ApiError::InvalidToken);
+    }
+
+    let program_id = session.program_id;
+    match request.action {
+        ApiAction::Progress(text) => log::info!("Program {program_id} progress: {text}"),
+        ApiAction::Event(payload) => {
+            let event = CustomEvent::new(program_id, &payload).ok_or(ApiError::PayloadTooLong)?;
+            if !session.limiter.accept("custom", limit, Instant::now()) {
+                return Err(ApiError::RateLimited(limit));
+            }
+            let tries = context.config.event_send_tries;
+            context.events.push(RetryEvent::new(Event::Custom(event), tries))?;
+            log::info!("Program {program_id} queued event {payload:02x?}");
+        }
+        ApiAction::Package => {
+            session.package_requested = true;
+            log::info!("Program {program_id} requested its result to be packed");
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    hex.as_bytes()
+        .chunks_exact(2)
+        .map(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::{ExecutionContext, MemoryNotifier},
+        config::Configuration,
+    };
+
+    #[test]
+    fn requests_are_parsed() {
+        let request = |token: &str, action| ApiRequest { token: token.to_string(), action };
+
+        assert_eq!(
+            "abc progress 50% done".parse(),
+            Ok(request("abc", ApiAction::Progress("50% done".into())))
+        );
+        assert_eq!("abc event 01ff".parse(), Ok(request("abc", ApiAction::Event(vec![1, 255]))));
+        assert_eq!("abc package".parse(), Ok(request("abc", ApiAction::Package)));
+        assert!("abc event 1".parse::<ApiRequest>().is_err());
+        assert!("abc package now".parse::<ApiRequest>().is_err());
+        assert!("abc".parse::<ApiRequest>().is_err());
+    }
+
+    #[test]
+    fn only_the_running_program_is_served() {
+        let config = Configuration {
+            events_path: "__api_events".into(),
+            fault_log_path: "__api_faults".into(),
+            ..Default::default()
+        };
+        let exec =
+            ExecutionContext::with_notifier(config, Box::new(MemoryNotifier::default())).unwrap();
+        let request = |token: &str, action| ApiRequest { token: token.to_string(), action };
+
+        let result = handle_request(request("abc", ApiAction::Package), &exec);
+        assert!(matches!(result, Err(ApiError::NotRunning)));
+
+        let session = ApiSession::new(7, 0).unwrap();
+        let token = session.token.clone();
+        assert_eq!(token.len(), 2 * TOKEN_LENGTH);
+        exec.lock().unwrap().api_session = Some(session);
+
+        let result = handle_request(request("abc", ApiAction::Package), &exec);
+        assert!(matches!(result, Err(ApiError::InvalidToken)));
+        let result = handle_request(request(&token, ApiAction::Event(vec![0; 17])), &exec);
+        assert!(matches!(result, Err(ApiError::PayloadTooLong)));
+
+        handle_request(request(&token, ApiAction::Event(vec![1, 2])), &exec).unwrap();
+        handle_request(request(&token, ApiAction::Package), &exec).unwrap();
+
+        let context = exec.lock().unwrap();
+        assert!(context.api_session.as_ref().unwrap().package_requested);
+        let expected = Event::Custom(CustomEvent::new(7, &[1, 2]).unwrap());
+        assert_eq!(context.events.as_ref()[0].event, expected);
+        drop(context);
+
+        let _ = std::fs::remove_file("__api_events");
+        let _ = std::fs::remove_file("__api_faults");
+    }
+
+    #[test]
+    fn events_of_a_run_are_limited() {
+        let config = Configuration {
+            events_path: "__api_limited_events".into(),
+            fault_log_path: "__api_limited_faults".into(),
+            socket_event_rate_limit: 2,
+            ..Default::default()
+        };
+        let exec =
+            ExecutionContext::with_notifier(config, Box::new(MemoryNotifier::default())).unwrap();
+        let session = ApiSession::new(7, 0).unwrap();
+        let event = format!("{} event 01", session.token);
+        exec.lock().unwrap().api_session = Some(session);
+
+        assert_eq!(respond(&event, &exec), "ok");
+        assert_eq!(respond(&event, &exec), "ok");
+        assert_eq!(respond(&event, &exec), "error: more than 2 events per minute");
+        assert_eq!(exec.lock().unwrap().events.as_ref().len(), 2);
+
+        // the next run starts with a fresh limit
+        let session = ApiSession::new(7, 1).unwrap();
+        let event = format!("{} event 01", session.token);
+        exec.lock().unwrap().api_session = Some(session);
+        assert_eq!(respond(&event, &exec), "ok");
+
+        let _ = std::fs::remove_file("__api_limited_events");
+        let _ = std::fs::remove_file("__api_limited_faults");
+    }
+}
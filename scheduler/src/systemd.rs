@@ -0,0 +1,115 @@
+use crate::watchdog::Watchdog;
+use std::{
+    os::{linux::net::SocketAddrExt, unix::net::UnixDatagram},
+    time::Duration,
+};
+
+/// Sends state changes to systemd through the socket given in `$NOTIFY_SOCKET`, see `sd_notify(3)`
+pub struct SystemdNotifier {
+    socket: UnixDatagram,
+    address: std::os::unix::net::SocketAddr,
+}
+
+impl SystemdNotifier {
+    /// Returns `None` if the scheduler was not started by systemd with `Type=notify`
+    pub fn from_env() -> std::io::Result<Option<Self>> {
+        match std::env::var_os("NOTIFY_SOCKET") {
+            Some(path) => Self::new(&path.to_string_lossy()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Connects to the given socket. Paths starting with `@` are in the abstract namespace.
+    pub fn new(path: &str) -> std::io::Result<Self> {
+        let address = match path.strip_prefix('@') {
+            Some(name) => std::os::unix::net::SocketAddr::from_abstract_name(name)?,
+            None => std::os::unix::net::SocketAddr::from_pathname(path)?,
+        };
+        Ok(Self { socket: UnixDatagram::unbound()?, address })
+    }
+
+    /// Sends newline separated assignments, e.g. `READY=1`
+    pub fn notify(&self, state: &str) -> std::io::Result<()> {
+        self.socket.send_to_addr(state.as_bytes(), &self.address)?;
+        Ok(())
+    }
+
+    /// Pets the systemd watchdog if all threads monitored by `watchdog` make progress. Otherwise
+    /// only the status is updated, so that systemd restarts the scheduler once `WatchdogSec` passed.
+    pub fn ping(&self, watchdog: &Watchdog) -> std::io::Result<()> {
+        let silent = watchdog.silent_threads();
+        if silent.is_empty() {
+            self.notify("WATCHDOG=1")
+        } else {
+            self.notify(&format!("STATUS=Threads {silent:?} do not respond"))
+        }
+    }
+}
+
+/// The interval in which systemd expects `WATCHDOG=1`, if the watchdog is enabled for this process
+#[must_use]
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) {
+        if pid != std::process::id() {
+            return None;
+        }
+    }
+
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+/// Pets the systemd watchdog twice per `interval`, as recommended by `sd_watchdog_enabled(3)`
+pub fn watchdog_loop(notifier: &SystemdNotifier, interval: Duration, watchdog: &Watchdog) -> ! {
+    loop {
+        if let Err(e) = notifier.ping(watchdog) {
+            log::warn!("Could not notify systemd: {e}");
+        }
+        std::thread::sleep(interval / 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receive(socket: &UnixDatagram) -> String {
+        let mut buffer = [0; 256];
+        let n = socket.recv(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer[..n]).into_owned()
+    }
+
+    #[test]
+    fn state_is_sent_to_socket() {
+        let path = "__systemd_notify";
+        let _ = std::fs::remove_file(path);
+        let socket = UnixDatagram::bind(path).unwrap();
+
+        let notifier = SystemdNotifier::new(path).unwrap();
+        notifier.notify("READY=1\nSTATUS=Running").unwrap();
+        assert_eq!(receive(&socket), "READY=1\nSTATUS=Running");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn watchdog_is_only_pet_while_threads_respond() {
+        let name = "__systemd_watchdog";
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name).unwrap();
+        let socket = UnixDatagram::bind_addr(&address).unwrap();
+        let notifier = SystemdNotifier::new(&format!("@{name}")).unwrap();
+
+        let watchdog = Watchdog::default();
+        let handle = watchdog.register("command", Duration::from_millis(10));
+        notifier.ping(&watchdog).unwrap();
+        assert_eq!(receive(&socket), "WATCHDOG=1");
+
+        std::thread::sleep(Duration::from_millis(20));
+        notifier.ping(&watchdog).unwrap();
+        assert_eq!(receive(&socket), "STATUS=Threads [\"command\"] do not respond");
+
+        handle.check_in();
+        notifier.ping(&watchdog).unwrap();
+        assert_eq!(receive(&socket), "WATCHDOG=1");
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{config::Configuration, watchdog::Watchdog};
+use std::{
+    io::Write,
+    os::unix::{fs::PermissionsExt, process::CommandExt},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// The length of the SHA-256 checksum an update is verified with
+pub const CHECKSUM_LENGTH: usize = 32;
+/// How often `supervise_trial` checks wether the new binary is still healthy
+const TRIAL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateError {
+    #[error("Checksum mismatch, expected {expected:02x?}, got {actual:02x?}")]
+    ChecksumMismatch { expected: Vec<u8>, actual: Vec<u8> },
+    #[error("No update is staged")]
+    NotStaged,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// The state the scheduler starts in, see [`Updater::resume_trial`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrialState {
+    /// The running binary is not on trial
+    Confirmed,
+    /// The running binary was just installed and has to prove that it is healthy
+    Running,
+    /// The installed binary did not survive its trial and the previous one was restored
+    RolledBack,
+}
+
+/// Replaces the scheduler binary with one uploaded by the COBC.
+///
+/// An update is first staged in the data directory. Installing it keeps the current binary as a
+/// backup and marks the new one as being on trial. If the new binary is restarted before it was
+/// confirmed, or does not stay healthy for the configured window, the backup is restored.
+pub struct Updater {
+    executable: PathBuf,
+    staged: PathBuf,
+    backup: PathBuf,
+    trial: PathBuf,
+}
+
+impl Updater {
+    /// Creates an updater for the given scheduler binary
+    #[must_use]
+    pub fn new(executable: impl Into<PathBuf>, config: &Configuration) -> Self {
+        let executable = executable.into();
+        Self {
+            staged: config.data_file("update"),
+            backup: with_suffix(&executable, ".backup"),
+            trial: config.data_file("update_trial"),
+            executable,
+        }
+    }
+
+    /// Creates an updater for the binary of the running process
+    pub fn for_current_executable(config: &Configuration) -> std::io::Result<Self> {
+        Ok(Self::new(std::env::current_exe()?, config))
+    }
+
+    #[must_use]
+    pub fn executable(&self) -> &Path {
+        &self.executable
+    }
+
+    /// Verifies the SHA-256 checksum of `binary` and stores it as the staged update, replacing a
+    /// previously staged one
+    pub fn stage(&self, binary: &[u8], checksum: &[u8]) -> Result<(), UpdateError> {
+        let actual = sha256(binary);
+        if actual != checksum {
+            return Err(UpdateError::ChecksumMismatch {
+                expected: checksum.to_vec(),
+                actual: actual.to_vec(),
+            });
+        }
+
+        write_executable(&self.staged, binary)?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_staged(&self) -> bool {
+        self.staged.is_file()
+    }
+
+    /// Replaces the executable with the staged update and puts it on trial. The previous
+    /// executable is kept as a backup until the update is confirmed.
+    pub fn install(&self) -> Result<(), UpdateError> {
+        if !self.is_staged() {
+            return Err(UpdateError::NotStaged);
+        }
+
+        // rename does not work across file systems, so the new binary is copied next to the old one
+        let new = with_suffix(&self.executable, ".new");
+        std::fs::copy(&self.staged, &new)?;
+        std::fs::write(&self.trial, b"0")?;
+        std::fs::rename(&self.executable, &self.backup)?;
+        std::fs::rename(&new, &self.executable)?;
+        std::fs::remove_file(&self.staged)?;
+
+        log::info!("Installed update, previous binary kept at {:?}", self.backup);
+        Ok(())
+    }
+
+    /// Must be called at startup. If the running binary is on trial and was already started
+    /// before, it did not survive its trial and the backup is restored.
+    pub fn resume_trial(&self) -> std::io::Result<TrialState> {
+        let starts = match std::fs::read_to_string(&self.trial) {
+            Ok(s) => s.trim().parse::<u32>().unwrap_or(u32::MAX),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(TrialState::Confirmed),
+            Err(e) => return Err(e),
+        };
+
+        if starts > 0 {
+            self.rollback()?;
+            return Ok(TrialState::RolledBack);
+        }
+
+        std::fs::write(&self.trial, (starts + 1).to_string())?;
+        Ok(TrialState::Running)
+    }
+
+    /// Ends the trial, keeping the new binary
+    pub fn confirm(&self) -> std::io::Result<()> {
+        remove_if_exists(&self.backup)?;
+        remove_if_exists(&self.trial)?;
+        log::info!("Update confirmed");
+        Ok(())
+    }
+
+    /// Ends the trial, restoring the previous binary
+    pub fn rollback(&self) -> std::io::Result<()> {
+        if self.backup.exists() {
+            std::fs::rename(&self.backup, &self.executable)?;
+        }
+        remove_if_exists(&self.trial)?;
+        log::warn!("Update rolled back");
+        Ok(())
+    }
+}
+
+/// Confirms the update once all threads monitored by `watchdog` kept making progress for `window`.
+/// If any of them goes silent before, the update is rolled back and false is returned, so that the
+/// caller can restart into the previous binary.
+#[must_use]
+pub fn supervise_trial(updater: &Updater, window: Duration, watchdog: &Watchdog) -> bool {
+    let deadline = Instant::now() + window;
+    while Instant::now() < deadline {
+        let silent = watchdog.silent_threads();
+        if !silent.is_empty() {
+            log::error!("Threads {silent:?} do not respond during the trial of the update");
+            if let Err(e) = updater.rollback() {
+                log::error!("Could not roll back update: {e}");
+                return true; // the update is still installed, restarting would not help
+            }
+            return false;
+        }
+        std::thread::sleep(
+            TRIAL_POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+        );
+    }
+
+    if let Err(e) = updater.confirm() {
+        log::error!("Could not confirm update: {e}");
+    }
+    true
+}
+
+/// Replaces the running process with the given executable, passing on all arguments. Only returns
+/// if this failed.
+#[must_use]
+pub fn restart(executable: &Path) -> std::io::Error {
+    log::logger().flush();
+    std::process::Command::new(executable).args(std::env::args_os().skip(1)).exec()
+}
+
+#[must_use]
+pub fn sha256(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes).into()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Writes the file atomically and makes it executable
+fn write_executable(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp_path = with_suffix(path, ".tmp");
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(bytes)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(name: &str) -> Updater {
+        let dir = PathBuf::from(format!("__update_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("scheduler"), b"old").unwrap();
+
+        let config = Configuration { data_path: dir.clone(), ..Default::default() };
+        Updater::new(dir.join("scheduler"), &config)
+    }
+
+    #[test]
+    fn update_with_wrong_checksum_is_not_staged() {
+        let updater = prepare("checksum");
+
+        let result = updater.stage(b"new", &sha256(b"other"));
+        assert!(matches!(result, Err(UpdateError::ChecksumMismatch { .. })));
+        assert!(!updater.is_staged());
+        assert!(matches!(updater.install(), Err(UpdateError::NotStaged)));
+
+        let _ = std::fs::remove_dir_all("__update_checksum");
+    }
+
+    #[test]
+    fn confirmed_update_is_kept() {
+        let updater = prepare("confirmed");
+
+        updater.stage(b"new", &sha256(b"new")).unwrap();
+        updater.install().unwrap();
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Running);
+        updater.confirm().unwrap();
+
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Confirmed);
+        assert_eq!(std::fs::read(updater.executable()).unwrap(), b"new");
+        let mode = std::fs::metadata(updater.executable()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        let _ = std::fs::remove_dir_all("__update_confirmed");
+    }
+
+    #[test]
+    fn update_restarted_during_trial_is_rolled_back() {
+        let updater = prepare("restarted");
+
+        updater.stage(b"new", &sha256(b"new")).unwrap();
+        updater.install().unwrap();
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Running);
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::RolledBack);
+
+        assert_eq!(std::fs::read(updater.executable()).unwrap(), b"old");
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Confirmed);
+
+        let _ = std::fs::remove_dir_all("__update_restarted");
+    }
+
+    #[test]
+    fn healthy_update_is_confirmed_by_supervisor() {
+        let updater = prepare("healthy");
+        let watchdog = Watchdog::default();
+        let handle = watchdog.register("command", Duration::from_secs(5));
+
+        updater.stage(b"new", &sha256(b"new")).unwrap();
+        updater.install().unwrap();
+        updater.resume_trial().unwrap();
+        assert!(supervise_trial(&updater, Duration::from_millis(200), &watchdog));
+        drop(handle);
+
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Confirmed);
+        assert_eq!(std::fs::read(updater.executable()).unwrap(), b"new");
+
+        let _ = std::fs::remove_dir_all("__update_healthy");
+    }
+
+    #[test]
+    fn update_with_silent_thread_is_rolled_back_by_supervisor() {
+        let updater = prepare("silent");
+        let watchdog = Watchdog::default();
+        let handle = watchdog.register("command", Duration::from_millis(10));
+
+        updater.stage(b"new", &sha256(b"new")).unwrap();
+        updater.install().unwrap();
+        updater.resume_trial().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!supervise_trial(&updater, Duration::from_secs(5), &watchdog));
+        drop(handle);
+
+        assert_eq!(std::fs::read(updater.executable()).unwrap(), b"old");
+        assert_eq!(updater.resume_trial().unwrap(), TrialState::Confirmed);
+
+        let _ = std::fs::remove_dir_all("__update_silent");
+    }
+}
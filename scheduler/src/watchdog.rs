@@ -0,0 +1,186 @@
+use crate::gpio::OutputPin;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Keeps track of wether the critical threads of the scheduler are still making progress. Each of
+/// them registers itself and has to check in periodically, otherwise it is reported as silent.
+///
+/// Threads that wait for something that may legitimately take forever, like the next command from
+/// the COBC, mark themselves as idle while waiting.
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    threads: HashMap<u64, Monitored>,
+}
+
+struct Monitored {
+    name: &'static str,
+    timeout: Duration,
+    last_check_in: Instant,
+    idle: bool,
+}
+
+impl Watchdog {
+    /// Starts monitoring a thread, which has to check in at least every `timeout`. The monitoring
+    /// ends once the returned handle is dropped.
+    #[must_use]
+    pub fn register(&self, name: &'static str, timeout: Duration) -> WatchdogHandle {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner
+            .threads
+            .insert(id, Monitored { name, timeout, last_check_in: Instant::now(), idle: false });
+
+        WatchdogHandle { watchdog: self.clone(), id }
+    }
+
+    /// Returns the names of all threads that did not check in within their timeout
+    #[must_use]
+    pub fn silent_threads(&self) -> Vec<&'static str> {
+        let inner = self.inner.lock().unwrap();
+        let mut silent: Vec<_> = inner
+            .threads
+            .values()
+            .filter(|t| !t.idle && t.last_check_in.elapsed() > t.timeout)
+            .map(|t| t.name)
+            .collect();
+        silent.sort_unstable();
+        silent
+    }
+
+    fn update(&self, id: u64, idle: bool) {
+        if let Some(thread) = self.inner.lock().unwrap().threads.get_mut(&id) {
+            thread.last_check_in = Instant::now();
+            thread.idle = idle;
+        }
+    }
+}
+
+/// Used by a monitored thread to report its progress
+pub struct WatchdogHandle {
+    watchdog: Watchdog,
+    id: u64,
+}
+
+impl WatchdogHandle {
+    pub fn check_in(&self) {
+        self.watchdog.update(self.id, false);
+    }
+
+    /// Suspends the monitoring until the next check in
+    pub fn idle(&self) {
+        self.watchdog.update(self.id, true);
+    }
+}
+
+impl Drop for WatchdogHandle {
+    fn drop(&mut self) {
+        self.watchdog.inner.lock().unwrap().threads.remove(&self.id);
+    }
+}
+
+/// Toggles the heartbeat pin as long as all threads monitored by a [`Watchdog`] make progress. If
+/// any of them goes silent, the heartbeat stops, so that the COBC power-cycles the EDU.
+pub struct Heartbeat {
+    pin: Box<dyn OutputPin>,
+    toggle_time: Duration,
+    silent: Vec<&'static str>,
+}
+
+impl Heartbeat {
+    /// `freq` is given in Hz
+    #[must_use]
+    pub fn new(pin: Box<dyn OutputPin>, freq: u64) -> Self {
+        Self { pin, toggle_time: Duration::from_millis(1000 / freq / 2), silent: Vec::new() }
+    }
+
+    /// Runs a single period, during which the pin is first high and then low. It stays low if a
+    /// thread is silent.
+    pub fn beat(&mut self, watchdog: &Watchdog) {
+        let silent = watchdog.silent_threads();
+        if silent != self.silent {
+            if silent.is_empty() {
+                log::info!("All threads respond again, resuming heartbeat");
+            } else {
+                log::error!("Threads {silent:?} do not respond, stopping heartbeat");
+            }
+            self.silent = silent;
+        }
+
+        if self.silent.is_empty() {
+            self.pin.set(true);
+        }
+        std::thread::sleep(self.toggle_time);
+        self.pin.set(false);
+        std::thread::sleep(self.toggle_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpio::{GpioBackend, SimulatedBackend};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn thread_that_does_not_check_in_is_silent() {
+        let watchdog = Watchdog::default();
+        let a = watchdog.register("a", TIMEOUT);
+        let b = watchdog.register("b", TIMEOUT);
+
+        std::thread::sleep(TIMEOUT * 2);
+        a.check_in();
+        assert_eq!(watchdog.silent_threads(), vec!["b"]);
+
+        b.check_in();
+        assert!(watchdog.silent_threads().is_empty());
+    }
+
+    #[test]
+    fn idle_and_dropped_threads_are_not_silent() {
+        let watchdog = Watchdog::default();
+        let a = watchdog.register("a", TIMEOUT);
+        let b = watchdog.register("b", TIMEOUT);
+
+        a.idle();
+        drop(b);
+        std::thread::sleep(TIMEOUT * 2);
+        assert!(watchdog.silent_threads().is_empty());
+
+        a.check_in();
+        std::thread::sleep(TIMEOUT * 2);
+        assert_eq!(watchdog.silent_threads(), vec!["a"]);
+    }
+
+    #[test]
+    fn heartbeat_stops_while_a_thread_is_silent() {
+        let gpio = SimulatedBackend::default();
+        let mut heartbeat = Heartbeat::new(gpio.output(34).unwrap(), 100);
+        let watchdog = Watchdog::default();
+        let handle = watchdog.register("command", TIMEOUT);
+
+        heartbeat.beat(&watchdog);
+        heartbeat.beat(&watchdog);
+        handle.check_in();
+        std::thread::sleep(TIMEOUT * 2);
+        heartbeat.beat(&watchdog);
+
+        let transitions = gpio.pins.lock().unwrap()[&34].clone();
+        let levels: Vec<_> = transitions.iter().map(|(_, level)| *level).collect();
+        assert_eq!(levels, [true, false, true, false]);
+        for pair in transitions.windows(2) {
+            let elapsed = pair[1].0 - pair[0].0;
+            assert!(elapsed >= Duration::from_millis(5), "{elapsed:?}");
+        }
+    }
+}
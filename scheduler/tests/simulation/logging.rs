@@ -10,20 +10,28 @@ fn logfile_is_created() {
 }
 
 #[test]
-fn logfile_is_cleared_after_sent() -> std::io::Result<()> {
-    let (_sched, mut com, _socat) = start_scheduler("log_is_cleared_after_sent").unwrap();
+fn result_contains_log_excerpt() -> std::io::Result<()> {
+    let (_sched, mut com, _socat) = start_scheduler("log_excerpt").unwrap();
 
     simulate_test_store_archive(&mut com, 1).unwrap();
     com.send_packet(&CEPPacket::Data(execute_program(1, 0, 3))).unwrap();
     com.await_ack(Duration::MAX).unwrap();
     std::thread::sleep(std::time::Duration::from_millis(100));
 
-    let _ = simulate_return_result(&mut com, 1, 0).unwrap();
+    let result = simulate_return_result(&mut com, 1, 0).unwrap();
     com.send_packet(&CEPPacket::Ack).unwrap();
     std::thread::sleep(std::time::Duration::from_millis(100));
 
-    let log_metadata = std::fs::metadata("./tests/tmp/log_is_cleared_after_sent/log")?;
-    assert!(log_metadata.len() < 100, "Logfile is not empty");
+    let excerpt = simple_archive::Reader::new(&result[..])
+        .map(Result::unwrap)
+        .find(|entry| entry.path == "log")
+        .unwrap();
+    let excerpt = String::from_utf8(excerpt.data).unwrap();
+    assert!(excerpt.contains("Program 1:0 finished"));
+    assert!(!excerpt.contains("Scheduler started"));
+
+    let log = std::fs::read_to_string("./tests/tmp/log_excerpt/log")?;
+    assert!(log.contains("Scheduler started"), "Log was cleared");
 
     Ok(())
 }
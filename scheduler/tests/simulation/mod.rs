@@ -71,8 +71,10 @@ fn get_config_str(unique: &str) -> String {
     heartbeat_pin = 34
     update_pin = 35
     heartbeat_freq = 10
+    gpio_backend = \"simulated\"
     log_path = \"log\"
     socket = \"/tmp/STS1_EDU_Scheduler_SIM_{unique}\"
+    api_socket = \"/tmp/STS1_EDU_Scheduler_SIM_API_{unique}\"
     "
     )
 }
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::time::Duration;
 
@@ -27,13 +27,43 @@ fn multiple_dosimeter_events() {
 
     let mut socket = UnixStream::connect("/tmp/STS1_EDU_Scheduler_SIM_dosimeter_multi").unwrap();
     for _ in 0..10 {
-        writeln!(socket, "dosimeter/on").unwrap();
-        writeln!(socket, "dosimeter/off").unwrap();
+        writeln!(socket, r#"{{"request": "event", "event": "dosimeter/on"}}"#).unwrap();
+        writeln!(socket, r#"{{"request": "event", "event": "dosimeter/off"}}"#).unwrap();
     }
 
+    // consecutive toggles are coalesced into the latest state
     std::thread::sleep(Duration::from_millis(200));
-    for _ in 0..10 {
-        assert_eq!(simulate_get_status(&mut com).unwrap(), [0x03]);
-        assert_eq!(simulate_get_status(&mut com).unwrap(), [0x04]);
-    }
+    assert_eq!(simulate_get_status(&mut com).unwrap(), [0x04]);
+    assert_eq!(simulate_get_status(&mut com).unwrap(), [0x00]);
+}
+
+#[test]
+fn statistics_are_returned_on_socket() {
+    let (_sched, mut com, _socat) = start_scheduler("socket_statistics").unwrap();
+
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(simulate_get_status(&mut com).unwrap(), [0x00]);
+
+    let mut socket = UnixStream::connect("/tmp/STS1_EDU_Scheduler_SIM_socket_statistics").unwrap();
+    writeln!(socket, r#"{{"request": "statistics"}}"#).unwrap();
+
+    let mut line = String::new();
+    BufReader::new(socket).read_line(&mut line).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(response["ok"]["session"]["packets_sent"], 2, "Unexpected response {line}");
+    assert_eq!(response["ok"]["session"]["packets_received"], 2, "Unexpected response {line}");
+}
+
+#[test]
+fn log_filter_is_changed_on_socket() {
+    let (_sched, _com, _socat) = start_scheduler("socket_log_filter").unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+
+    let mut socket = UnixStream::connect("/tmp/STS1_EDU_Scheduler_SIM_socket_log_filter").unwrap();
+    writeln!(socket, r#"{{"request": "log_filter", "directives": "warn,communication=debug"}}"#)
+        .unwrap();
+
+    let mut line = String::new();
+    BufReader::new(socket).read_line(&mut line).unwrap();
+    assert_eq!(line.trim_end(), r#"{"ok":"warn,communication=debug"}"#);
 }
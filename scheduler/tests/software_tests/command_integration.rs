@@ -1,7 +1,8 @@
 use crate::software_tests::common;
 use crate::software_tests::common::ComEvent::*;
-use STS1_EDU_Scheduler::command;
-use STS1_EDU_Scheduler::communication::CEPPacket::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self, NackReason, SUPPORTED_COMMANDS};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
 
 #[test]
 fn invalid_packets_from_cobc() {
@@ -25,22 +26,132 @@ fn invalid_packets_from_cobc() {
 }
 
 #[test]
-#[should_panic]
-fn ack_on_start_panic() {
-    let (mut com, mut exec) = common::prepare_handles(vec![Cobc(Ack)], "99");
-    command::handle_command(&mut com, &mut exec);
+fn unknown_command_is_nacked_if_reasons_are_negotiated() {
+    let packets = vec![
+        Cobc(Data(vec![0xee])),
+        Edu(Ack),
+        Cobc(Data(vec![0xee])),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::UnknownCommand as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "22");
+
+    assert!(!command::handle_command(&mut com, &mut exec));
+    com.capabilities = Capabilities::NACK_REASON;
+    assert!(!command::handle_command(&mut com, &mut exec));
+    assert!(com.is_complete());
+
+    common::cleanup("22");
 }
 
 #[test]
-#[should_panic]
-fn nack_on_start_panic() {
-    let (mut com, mut exec) = common::prepare_handles(vec![Cobc(Nack)], "99");
-    command::handle_command(&mut com, &mut exec);
+fn nack_reason_is_sent_if_negotiated() {
+    let packets = vec![
+        Cobc(Data(vec![1, 2])),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::InvalidLength as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(execute_program(99, 0, 2))),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::ProgramNotFound as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(return_result(99, 0))),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::ResultNotFound as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "23");
+    com.capabilities = Capabilities::NACK_REASON;
+
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("23");
 }
 
 #[test]
-#[should_panic]
-fn eof_on_start_panic() {
-    let (mut com, mut exec) = common::prepare_handles(vec![Cobc(Eof)], "99");
+fn exactly_supported_commands_are_dispatched() {
+    let mut packets = Vec::new();
+    for id in 0..=u8::MAX {
+        let reason = if SUPPORTED_COMMANDS.contains(&id) {
+            NackReason::InvalidLength
+        } else {
+            NackReason::UnknownCommand
+        };
+        packets.extend([Edu(Nack), Edu(Data(vec![0x01, reason as u8, 0, 0])), Cobc(Ack)]);
+    }
+    let (mut com, mut exec) = common::prepare_handles(packets, "48");
+    com.capabilities = Capabilities::NACK_REASON;
+
+    // Every handler rejects a command of this length before acting on it
+    for id in 0..=u8::MAX {
+        let _ = command::process_command(&[id; 2048], &mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("48");
+}
+
+#[test]
+fn non_data_packets_on_start_are_ignored() {
+    let packets =
+        vec![Cobc(Ack), Cobc(Nack), Cobc(Eof), Cobc(Data(stop_program())), Edu(Ack), Edu(Ack)];
+    let (mut com, mut exec) = common::prepare_handles(packets, "24");
+
+    for _ in 0..4 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("24");
+}
+
+#[test]
+fn communication_fault_is_recovered_and_logged() {
+    let packets = vec![Fail, Cobc(Data(stop_program())), Edu(Ack), Edu(Ack)];
+    let (mut com, mut exec) = common::prepare_handles(packets, "25");
+
+    command::handle_command(&mut com, &mut exec);
     command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    let l_exec = exec.lock().unwrap();
+    let records = l_exec.fault_log.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].command, 0); // no command was received yet
+    assert_eq!(records[0].class, 3); // non-recoverable
+    assert_eq!(records[0].fault, 3); // communication
+    drop(l_exec);
+
+    common::cleanup("25");
+}
+
+#[test]
+fn successful_command_resets_fault_count() {
+    let packets = vec![Fail, Fail, Cobc(Data(stop_program())), Edu(Ack), Edu(Ack), Fail, Fail];
+    let (mut com, mut exec) = common::prepare_handles(packets, "26");
+
+    for _ in 0..5 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("26");
+}
+
+#[test]
+#[should_panic(expected = "Aborting now")]
+fn repeated_faults_cause_restart() {
+    let (mut com, mut exec) = common::prepare_handles(vec![Fail, Fail, Fail], "99");
+
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
 }
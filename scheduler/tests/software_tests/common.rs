@@ -7,7 +7,12 @@ use std::{
 
 use STS1_EDU_Scheduler::{
     command::{ExecutionContext, SyncExecutionContext},
-    communication::{CEPPacket, ComResult, CommunicationHandle},
+    communication::{
+        CEPPacket, Capabilities, ComResult, CommunicationError, CommunicationHandle, Counter,
+        SharedComStatistics,
+    },
+    config::Configuration,
+    gpio::SimulatedBackend,
 };
 
 pub enum ComEvent {
@@ -21,6 +26,8 @@ pub enum ComEvent {
     Any,
     /// EDU shall send a packet, which is then passed to a given function (e.g. to allow for further checks on data)
     Action(Box<dyn Fn(&CEPPacket)>),
+    /// EDU shall want to receive a packet, but the underlying connection fails
+    Fail,
 }
 
 impl Debug for ComEvent {
@@ -31,6 +38,7 @@ impl Debug for ComEvent {
             Self::Sleep(arg0) => f.debug_tuple("SLEEP").field(arg0).finish(),
             Self::Any => write!(f, "ANY"),
             Self::Action(_) => f.debug_tuple("ACTION").finish(),
+            Self::Fail => write!(f, "FAIL"),
         }
     }
 }
@@ -39,17 +47,22 @@ impl Debug for ComEvent {
 /// checked against the supplied expected events vector
 pub struct TestCom {
     expected_events: VecDeque<ComEvent>,
+    pub statistics: SharedComStatistics,
+    pub capabilities: Capabilities,
 }
 
 impl CommunicationHandle for TestCom {
     fn send_packet(&mut self, packet: &CEPPacket) -> ComResult<()> {
         println!("Sent {packet:?}");
+        self.count(Counter::PacketSent);
         match self.expected_events.pop_front().unwrap() {
             ComEvent::Edu(p) => assert_eq!(&p, packet),
             ComEvent::Sleep(d) => std::thread::sleep(d),
             ComEvent::Any => (),
             ComEvent::Action(f) => f(packet),
-            event @ ComEvent::Cobc(_) => panic!("Expected {event:?} instead of send_packet"),
+            event @ (ComEvent::Cobc(_) | ComEvent::Fail) => {
+                panic!("Expected {event:?} instead of send_packet")
+            }
         }
 
         if matches!(packet, CEPPacket::Data(_)) {
@@ -63,8 +76,13 @@ impl CommunicationHandle for TestCom {
         match self.expected_events.pop_front().unwrap() {
             ComEvent::Cobc(p) => {
                 println!("Received {p:?}");
-                if matches!(p, CEPPacket::Data(_)) {
-                    self.send_packet(&CEPPacket::Ack)?;
+                self.count(Counter::PacketReceived);
+                match p {
+                    CEPPacket::Data(_) => self.send_packet(&CEPPacket::Ack)?,
+                    CEPPacket::WindowData(seq, _) => {
+                        self.send_packet(&CEPPacket::WindowAck(seq))?;
+                    }
+                    _ => (),
                 }
                 Ok(p)
             }
@@ -72,6 +90,7 @@ impl CommunicationHandle for TestCom {
                 std::thread::sleep(d);
                 self.receive_packet()
             }
+            ComEvent::Fail => Err(CommunicationError::Io(std::io::ErrorKind::BrokenPipe.into())),
             event => panic!("Expected {event:?} instead of receive_packet"),
         }
     }
@@ -80,11 +99,27 @@ impl CommunicationHandle for TestCom {
     const UNLIMITED_TIMEOUT: std::time::Duration = Duration::MAX;
 
     fn set_timeout(&mut self, _timeout: std::time::Duration) {}
+
+    fn statistics(&self) -> Option<&SharedComStatistics> {
+        Some(&self.statistics)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
 }
 
 impl TestCom {
     pub fn new(packets: Vec<ComEvent>) -> Self {
-        TestCom { expected_events: packets.into() }
+        TestCom {
+            expected_events: packets.into(),
+            statistics: SharedComStatistics::default(),
+            capabilities: Capabilities::NONE,
+        }
     }
 
     pub fn is_complete(&self) -> bool {
@@ -129,7 +164,16 @@ pub fn prepare_handles(packets: Vec<ComEvent>, unique: &str) -> (TestCom, SyncEx
     file_per_thread_logger::allow_uninitialized();
     file_per_thread_logger::initialize("tests/tmp/log-");
     let com = TestCom::new(packets);
-    let exec = ExecutionContext::new(format!("tests/tmp/{unique}"), 12).unwrap();
+    let exec = ExecutionContext::new(
+        Configuration {
+            update_pin: 12,
+            events_path: format!("tests/tmp/{unique}").into(),
+            fault_log_path: format!("tests/tmp/{unique}_faults").into(),
+            ..Default::default()
+        },
+        &SimulatedBackend::default(),
+    )
+    .unwrap();
 
     (com, exec)
 }
@@ -138,6 +182,8 @@ pub fn cleanup(unique: &str) {
     let _ = std::fs::remove_dir_all(format!("./archives/{unique}"));
     let _ = std::fs::remove_file(format!("tests/tmp/{unique}_s"));
     let _ = std::fs::remove_file(format!("tests/tmp/{unique}_r"));
+    let _ = std::fs::remove_file(format!("tests/tmp/{unique}_faults"));
+    let _ = std::fs::remove_dir_all(format!("./store/{unique}"));
 }
 
 #[allow(dead_code)]
@@ -169,3 +215,104 @@ pub fn return_result(program_id: u16, timestamp: u32) -> Vec<u8> {
     vec.extend(timestamp.to_le_bytes());
     vec
 }
+
+pub fn return_result_windowed(program_id: u16, timestamp: u32, window: u8) -> Vec<u8> {
+    let mut vec = return_result(program_id, timestamp);
+    vec.push(window);
+    vec
+}
+
+pub fn get_com_statistics() -> Vec<u8> {
+    vec![7u8]
+}
+
+pub fn negotiate_capabilities(capabilities: u8) -> Vec<u8> {
+    vec![8u8, capabilities]
+}
+
+pub fn get_capabilities() -> Vec<u8> {
+    vec![9u8]
+}
+
+pub fn get_fault_log(clear: bool) -> Vec<u8> {
+    vec![10u8, u8::from(clear)]
+}
+
+#[allow(dead_code)]
+pub fn return_log(index: u8) -> Vec<u8> {
+    vec![11u8, index]
+}
+
+#[allow(dead_code)]
+pub fn get_config() -> Vec<u8> {
+    vec![13u8]
+}
+
+#[allow(dead_code)]
+pub fn get_config_key(key: &str) -> Vec<u8> {
+    let mut vec = vec![13u8];
+    vec.extend(key.as_bytes());
+    vec
+}
+
+#[allow(dead_code)]
+pub fn set_config(assignment: &str) -> Vec<u8> {
+    let mut vec = vec![14u8];
+    vec.extend(assignment.as_bytes());
+    vec
+}
+
+#[allow(dead_code)]
+pub fn set_log_level(level: u8, module: &str) -> Vec<u8> {
+    let mut vec = vec![12u8, level];
+    vec.extend(module.as_bytes());
+    vec
+}
+
+#[allow(dead_code)]
+pub fn store_update(checksum: &[u8]) -> Vec<u8> {
+    let mut vec = vec![15u8];
+    vec.extend(checksum);
+    vec
+}
+
+#[allow(dead_code)]
+pub fn activate_update() -> Vec<u8> {
+    vec![16u8]
+}
+
+#[allow(dead_code)]
+pub fn store_archive_signed(program_id: u16, signature: &[u8]) -> Vec<u8> {
+    let mut vec = store_archive(program_id);
+    vec.extend(signature);
+    vec
+}
+
+#[allow(dead_code)]
+pub fn get_trusted_keys() -> Vec<u8> {
+    vec![17u8]
+}
+
+#[allow(dead_code)]
+pub fn get_program_store(program_id: u16) -> Vec<u8> {
+    let mut vec = vec![18u8];
+    vec.extend(program_id.to_le_bytes());
+    vec
+}
+
+#[allow(dead_code)]
+pub fn clear_program_store(program_id: u16) -> Vec<u8> {
+    let mut vec = vec![19u8];
+    vec.extend(program_id.to_le_bytes());
+    vec
+}
+
+#[allow(dead_code)]
+pub fn pause_program() -> Vec<u8> {
+    vec![20u8]
+}
+
+#[allow(dead_code)]
+pub fn resume_program() -> Vec<u8> {
+    vec![21u8]
+}
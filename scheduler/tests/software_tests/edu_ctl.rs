@@ -0,0 +1,79 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    process::{Command, Output},
+};
+
+/// Runs edu-ctl with a configuration that consists of `config` and the given control socket.
+/// Requests on the socket are answered with `response`, the received request is returned.
+fn run_edu_ctl(unique: &str, config: &str, args: &[&str], response: &str) -> (Output, String) {
+    let _ = std::fs::create_dir("tests/tmp");
+    let socket = format!("tests/tmp/{unique}_socket");
+    let config_path = format!("tests/tmp/{unique}_config.toml");
+    let _ = std::fs::remove_file(&socket);
+    std::fs::write(&config_path, format!("socket = {socket:?}\n{config}")).unwrap();
+
+    let listener = UnixListener::bind(&socket).unwrap();
+    let response = response.to_string();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut request = String::new();
+        let _ = BufReader::new(&stream).read_line(&mut request);
+        let _ = writeln!(&stream, "{response}");
+        request
+    });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_edu-ctl"))
+        .args(["--config", &config_path])
+        .args(args)
+        .output()
+        .unwrap();
+    let _ = UnixStream::connect(&socket); // releases the server if edu-ctl never connected
+    let request = server.join().unwrap();
+
+    let _ = std::fs::remove_file(&socket);
+    let _ = std::fs::remove_file(&config_path);
+    (output, request)
+}
+
+#[test]
+fn request_is_sent_and_answer_printed() {
+    let (output, request) =
+        run_edu_ctl("ctl1", "", &["execute", "7", "3", "10"], r#"{"ok":"Started"}"#);
+
+    assert!(output.status.success());
+    let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+    assert_eq!(
+        request,
+        serde_json::json!({ "request": "execute", "program_id": 7, "timestamp": 3, "timeout": 10 })
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Started\n");
+}
+
+#[test]
+fn invalid_configuration_keys_are_reported_but_not_fatal() {
+    let config = "unknown_key = 1\nbaudrate = \"fast\"\n";
+    let (output, _) = run_edu_ctl("ctl2", config, &["stop"], r#"{"ok":null}"#);
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown_key"), "{stderr}");
+    assert!(stderr.contains("baudrate"), "{stderr}");
+}
+
+#[test]
+fn error_response_fails() {
+    let (output, _) = run_edu_ctl("ctl3", "", &["stop"], r#"{"error":"No program is running"}"#);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No program is running"));
+}
+
+#[test]
+fn invalid_command_prints_usage() {
+    let output =
+        Command::new(env!("CARGO_BIN_EXE_edu-ctl")).args(["execute", "seven"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage: edu-ctl"));
+}
@@ -0,0 +1,28 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self};
+use STS1_EDU_Scheduler::communication::CEPPacket::*;
+
+#[test]
+fn get_com_statistics_counts_packets() {
+    let packets = vec![
+        Cobc(Data(get_com_statistics())),
+        Edu(Ack),
+        Action(Box::new(|packet| {
+            let Data(data) = packet else { panic!("Expected data packet, got {packet:?}") };
+            assert_eq!(data.len(), 48);
+            assert_eq!(data[0..4], 1u32.to_le_bytes()); // session: sent
+            assert_eq!(data[4..8], 1u32.to_le_bytes()); // session: received
+            assert!(data[8..24].iter().all(|b| *b == 0));
+        })),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "16");
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.statistics.lock().unwrap().session.packets_received, 2);
+
+    common::cleanup("16");
+}
@@ -0,0 +1,83 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self};
+use STS1_EDU_Scheduler::communication::CEPPacket::*;
+use STS1_EDU_Scheduler::config::Configuration;
+
+#[test]
+fn returns_effective_configuration() {
+    let packets = vec![
+        Cobc(Data(get_config())),
+        Edu(Ack),
+        Action(Box::new(|packet| {
+            let Data(data) = packet else { panic!("Expected data packet, got {packet:?}") };
+            let config = Configuration::from_toml(std::str::from_utf8(data).unwrap()).unwrap();
+            assert_eq!(config.update_pin, 12);
+            assert_eq!(config.events_path, std::path::Path::new("tests/tmp/33"));
+            assert_eq!(config.python, Configuration::default().python);
+        })),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "33");
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("33");
+}
+
+#[test]
+fn returns_single_key() {
+    let packets = vec![
+        Cobc(Data(get_config_key("event_send_tries"))),
+        Edu(Ack),
+        Edu(Data(b"event_send_tries = 5".to_vec())),
+        Cobc(Ack),
+        Cobc(Data(get_config_key("unknown"))),
+        Edu(Ack),
+        Edu(Nack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "35");
+
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("35");
+}
+
+#[test]
+fn set_config_is_applied_and_persisted() {
+    let packets = vec![
+        Cobc(Data(set_config("event_send_tries = 2"))),
+        Edu(Ack),
+        Edu(Data(vec![0])), // applied immediately
+        Cobc(Ack),
+        Cobc(Data(set_config("heartbeat_freq = 20"))),
+        Edu(Ack),
+        Edu(Data(vec![1])), // requires a restart
+        Cobc(Ack),
+        Cobc(Data(set_config("heartbeat_freq = 0"))),
+        Edu(Ack),
+        Edu(Nack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "36");
+    std::fs::write("tests/tmp/36_config.toml", "").unwrap();
+    exec.lock().unwrap().config.path = Some("tests/tmp/36_config.toml".into());
+
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    let config = exec.lock().unwrap().config.clone();
+    assert_eq!(config.event_send_tries, 2);
+    assert_eq!(config.heartbeat_freq, 10);
+    let persisted = Configuration::load("tests/tmp/36_config.toml").unwrap();
+    assert_eq!(persisted.event_send_tries, 2);
+    assert_eq!(persisted.heartbeat_freq, 20);
+
+    let _ = std::fs::remove_file("tests/tmp/36_config.toml");
+    common::cleanup("36");
+}
@@ -0,0 +1,50 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self};
+use STS1_EDU_Scheduler::communication::CEPPacket::*;
+
+#[test]
+fn failed_command_is_logged_and_cleared() {
+    let packets = vec![
+        Cobc(Data(vec![3, 1])), // StopProgram with invalid length
+        Edu(Ack),
+        Edu(Nack),
+        Cobc(Data(get_fault_log(true))),
+        Edu(Ack),
+        Action(Box::new(|packet| {
+            let Data(data) = packet else { panic!("Expected data packet, got {packet:?}") };
+            assert_eq!(data.len(), 2 + 9);
+            assert_eq!(data[0..2], 1u16.to_le_bytes());
+            assert_eq!(data[6..], [0x03, 1, 0, 0, 0]); // command, class, fault, detail
+        })),
+        Cobc(Ack),
+        Cobc(Data(get_fault_log(false))),
+        Edu(Ack),
+        Edu(Data(vec![0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "27");
+
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+    assert!(exec.lock().unwrap().fault_log.records().is_empty());
+
+    common::cleanup("27");
+}
+
+#[test]
+fn fault_log_is_persisted() {
+    let (mut com, mut exec) = common::prepare_handles(vec![Cobc(Data(vec![0xff])), Edu(Ack)], "28");
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    drop(exec);
+
+    let log = command::FaultLog::open("tests/tmp/28_faults").unwrap();
+    assert_eq!(log.records().len(), 1);
+    assert_eq!(log.records()[0].command, 0xff);
+
+    common::cleanup("28");
+}
@@ -1,8 +1,17 @@
 use crate::software_tests::common;
 use crate::software_tests::common::ComEvent::*;
 use common::*;
-use STS1_EDU_Scheduler::command::{self};
+use std::sync::atomic::Ordering;
+use STS1_EDU_Scheduler::command::{
+    self, Event, EventType, ExecutionContext, MemoryNotifier, RetryEvent,
+};
 use STS1_EDU_Scheduler::communication::CEPPacket::*;
+use STS1_EDU_Scheduler::config::Configuration;
+use STS1_EDU_Scheduler::gpio::SimulatedBackend;
+
+fn external(name: &str) -> Event {
+    EventType::by_name(name).unwrap().event(&serde_json::Map::new()).unwrap()
+}
 
 #[test]
 fn get_status_none() {
@@ -74,3 +83,57 @@ fn get_status_priority_for_status() {
 
     common::cleanup("15");
 }
+
+#[test]
+fn notifier_is_reset_once_all_events_are_fetched() {
+    let packets = vec![
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![3])), // Enable Dosimeter
+        Cobc(Ack),
+    ];
+    let mut com = TestCom::new(packets);
+    let notifier = MemoryNotifier::default();
+    let config = Configuration {
+        events_path: "tests/tmp/41".into(),
+        fault_log_path: "tests/tmp/41_faults".into(),
+        ..Default::default()
+    };
+    let mut exec = ExecutionContext::with_notifier(config, Box::new(notifier.clone())).unwrap();
+
+    exec.lock().unwrap().events.push(RetryEvent::new(external("dosimeter/on"), 1)).unwrap();
+    assert!(notifier.pending.load(Ordering::Relaxed));
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(!notifier.pending.load(Ordering::Relaxed));
+
+    common::cleanup("41");
+}
+
+#[test]
+fn update_pin_follows_event_queue() {
+    let packets = vec![
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![4])), // Disable Dosimeter
+        Cobc(Ack),
+    ];
+    let mut com = TestCom::new(packets);
+    let gpio = SimulatedBackend::default();
+    let config = Configuration {
+        update_pin: 12,
+        events_path: "tests/tmp/42".into(),
+        fault_log_path: "tests/tmp/42_faults".into(),
+        ..Default::default()
+    };
+    let mut exec = ExecutionContext::new(config, &gpio).unwrap();
+
+    exec.lock().unwrap().events.push(RetryEvent::new(external("dosimeter/off"), 1)).unwrap();
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    let levels: Vec<_> = gpio.pins.lock().unwrap()[&12].iter().map(|(_, level)| *level).collect();
+    assert_eq!(levels, [false, true, false]);
+
+    common::cleanup("42");
+}
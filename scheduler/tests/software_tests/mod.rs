@@ -1,8 +1,19 @@
 mod command_integration;
 pub mod common;
 mod communication_tests;
+mod edu_ctl;
 mod execute_program;
+mod get_com_statistics;
+mod get_config;
+mod get_fault_log;
 mod get_status;
+mod negotiate_capabilities;
+mod pause_program;
+mod program_store;
+mod return_log;
 mod return_result;
+mod set_log_level;
 mod stop_program;
 mod store_archive;
+mod student_api;
+mod update;
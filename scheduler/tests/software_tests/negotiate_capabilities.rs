@@ -0,0 +1,102 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self, NackReason, BUILD_HASH, SUPPORTED_COMMANDS};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
+
+#[test]
+fn only_supported_capabilities_are_negotiated() {
+    let packets = vec![
+        Cobc(Data(negotiate_capabilities(0xff))),
+        Edu(Ack),
+        Edu(Data(vec![Capabilities::SUPPORTED.0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "19");
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.capabilities, Capabilities::SUPPORTED);
+
+    common::cleanup("19");
+}
+
+#[test]
+fn no_capabilities_are_negotiated_for_old_cobc() {
+    let packets =
+        vec![Cobc(Data(negotiate_capabilities(0x00))), Edu(Ack), Edu(Data(vec![0x00])), Cobc(Ack)];
+    let (mut com, mut exec) = common::prepare_handles(packets, "20");
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.capabilities, Capabilities::NONE);
+
+    common::cleanup("20");
+}
+
+#[test]
+fn new_session_starts_without_capabilities() {
+    let packets = vec![
+        Cobc(Data(negotiate_capabilities(0x00))), // handshake of a rebooted COBC
+        Edu(Ack),
+        Edu(Data(vec![0x00])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "51");
+
+    com.capabilities = Capabilities::SUPPORTED;
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.capabilities, Capabilities::NONE);
+
+    common::cleanup("51");
+}
+
+#[test]
+fn malformed_handshake_keeps_capabilities() {
+    let packets = vec![
+        Cobc(Data(vec![0x08])), // too short
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::InvalidLength as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "52");
+
+    com.capabilities = Capabilities::NACK_REASON;
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.capabilities, Capabilities::NACK_REASON);
+
+    common::cleanup("52");
+}
+
+#[test]
+fn get_capabilities_describes_scheduler() {
+    let packets = vec![
+        Cobc(Data(get_capabilities())),
+        Edu(Ack),
+        Action(Box::new(|packet| {
+            let Data(data) = packet else { panic!("Expected data packet, got {packet:?}") };
+            let n = usize::from(data[1]);
+            assert_eq!(data[0], 1);
+            assert_eq!(&data[2..2 + n], SUPPORTED_COMMANDS);
+            assert!(SUPPORTED_COMMANDS.contains(&0x09));
+
+            let rest = &data[2 + n..];
+            assert_eq!(u16::from_le_bytes([rest[0], rest[1]]), 11 * 1024);
+            assert_eq!(rest[2], Capabilities::SUPPORTED.0);
+            assert_eq!(rest[3], 0x03);
+            assert_eq!(&rest[5..], BUILD_HASH.as_bytes());
+        })),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "21");
+
+    com.capabilities = Capabilities::NACK_REASON;
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(com.capabilities, Capabilities::NACK_REASON);
+
+    common::cleanup("21");
+}
@@ -0,0 +1,121 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use std::time::Duration;
+use STS1_EDU_Scheduler::command::{self, group_is_alive, NackReason};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
+use STS1_EDU_Scheduler::student_api::ApiSession;
+
+#[test]
+fn paused_time_does_not_count_towards_timeout() {
+    let packets = vec![
+        Cobc(Data(execute_program(46, 1, 2))), // Execute Program 46, Queue 1, Timeout 2s
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(pause_program())),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![6, 46, 0, 1, 0, 0, 0, 1])), // Paused
+        Cobc(Ack),
+        Cobc(Data(resume_program())),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(resume_program())), // already running
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![1, 46, 0, 1, 0, 0, 0, 255])), // Killed after the timeout
+        Cobc(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![6, 46, 0, 1, 0, 0, 0, 0])), // Resumed
+        Cobc(Ack),
+    ];
+    common::prepare_program("46");
+    let (mut com, mut exec) = common::prepare_handles(packets, "46");
+
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    std::thread::sleep(Duration::from_secs(3));
+    assert!(exec.lock().unwrap().is_student_program_running());
+
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(!exec.lock().unwrap().paused);
+    std::thread::sleep(Duration::from_secs(4));
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("46");
+}
+
+#[test]
+fn pause_without_running_program() {
+    let packets = vec![Cobc(Data(pause_program())), Edu(Ack), Edu(Nack)];
+    let (mut com, mut exec) = common::prepare_handles(packets, "47");
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("47");
+}
+
+#[test]
+fn pause_of_exited_program_is_not_running() {
+    let packets = vec![
+        Cobc(Data(pause_program())),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::ProgramNotRunning as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "58");
+
+    // the program exited, but its supervisor did not clean up yet
+    let mut exited = std::process::Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    let mut con = exec.lock().unwrap();
+    con.student_pid = Some(exited.id());
+    con.api_session = Some(ApiSession::new(58, 0).unwrap());
+    drop(con);
+
+    com.capabilities = Capabilities::NACK_REASON;
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(!exec.lock().unwrap().paused);
+
+    common::cleanup("58");
+}
+
+#[test]
+fn stopped_program_leaves_no_process_behind() {
+    let packets = vec![
+        Cobc(Data(execute_program(49, 9, 10))), // Execute Program 49, Queue 9, Timeout 10s
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(pause_program())),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(stop_program())),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+    common::prepare_program("49");
+    let (mut com, mut exec) = common::prepare_handles(packets, "49");
+
+    command::handle_command(&mut com, &mut exec);
+    std::thread::sleep(Duration::from_millis(500)); // the program starts a child in the meantime
+    command::handle_command(&mut com, &mut exec);
+    let pid = exec.lock().unwrap().student_pid.unwrap();
+    assert!(group_is_alive(pid));
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(!group_is_alive(pid));
+
+    common::cleanup("49");
+}
@@ -0,0 +1,107 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use std::time::Duration;
+use STS1_EDU_Scheduler::command;
+use STS1_EDU_Scheduler::communication::CEPPacket::*;
+
+/// The store entry "counter" with the given value, as sent by Get Program Store
+fn counter(value: &[u8]) -> Vec<u8> {
+    let mut v = vec![7];
+    v.extend(b"counter");
+    v.extend(u32::try_from(value.len()).unwrap().to_le_bytes());
+    v.extend(value);
+    v
+}
+
+#[test]
+fn store_persists_between_executions() {
+    let packets = vec![
+        Cobc(Data(execute_program(43, 6, 5))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(execute_program(43, 6, 5))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_program_store(43))),
+        Edu(Ack),
+        Edu(Data(counter(b"2"))),
+        Cobc(Ack),
+        Edu(Eof),
+        Cobc(Ack),
+        Cobc(Data(clear_program_store(43))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_program_store(43))),
+        Edu(Ack),
+        Edu(Eof),
+        Cobc(Ack),
+    ];
+    common::prepare_program("43");
+    let (mut com, mut exec) = common::prepare_handles(packets, "43");
+
+    for _ in 0..2 {
+        command::handle_command(&mut com, &mut exec);
+        std::thread::sleep(Duration::from_secs(1));
+    }
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("43");
+}
+
+#[test]
+fn changes_exceeding_quota_are_discarded() {
+    let packets = vec![
+        Cobc(Data(execute_program(44, 6, 5))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(execute_program(44, 7, 5))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_program_store(44))),
+        Edu(Ack),
+        Edu(Data(counter(b"1"))),
+        Cobc(Ack),
+        Edu(Eof),
+        Cobc(Ack),
+    ];
+    common::prepare_program("44");
+    let (mut com, mut exec) = common::prepare_handles(packets, "44");
+    exec.lock().unwrap().config.store_quota = 1000;
+
+    for _ in 0..2 {
+        command::handle_command(&mut com, &mut exec);
+        std::thread::sleep(Duration::from_secs(1));
+    }
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("44");
+}
+
+#[test]
+fn program_exceeding_quota_is_stopped() {
+    let packets = vec![
+        Cobc(Data(execute_program(59, 10, 10))), // Execute Program 59, Queue 10, Timeout 10s
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![1, 59, 0, 10, 0, 0, 0, 255])),
+        Cobc(Ack),
+    ];
+    common::prepare_program("59");
+    let (mut com, mut exec) = common::prepare_handles(packets, "59");
+    exec.lock().unwrap().config.store_quota = 1000;
+
+    command::handle_command(&mut com, &mut exec);
+    std::thread::sleep(Duration::from_secs(4)); // well before the timeout
+    assert!(exec.lock().unwrap().student_pid.is_none());
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("59");
+}
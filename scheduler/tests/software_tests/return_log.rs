@@ -0,0 +1,48 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self, NackReason};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
+use STS1_EDU_Scheduler::config::Configuration;
+use STS1_EDU_Scheduler::logging;
+
+#[test]
+fn returns_rotated_log() {
+    let config = Configuration::default();
+    let path = logging::rotated_log_path(&config.log_path, config.log_files);
+    std::fs::write(&path, "old log line\n").unwrap();
+
+    let packets = vec![
+        Cobc(Data(return_log(config.log_files))),
+        Edu(Ack),
+        Edu(Data(b"old log line\n".to_vec())),
+        Cobc(Ack),
+        Edu(Eof),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "29");
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    let _ = std::fs::remove_file(path);
+    common::cleanup("29");
+}
+
+#[test]
+fn missing_log_is_nacked() {
+    let packets = vec![
+        Cobc(Data(return_log(Configuration::default().log_files + 1))),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::LogNotFound as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "30");
+    com.capabilities = Capabilities::NACK_REASON;
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("30");
+}
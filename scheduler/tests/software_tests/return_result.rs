@@ -1,3 +1,4 @@
+#![allow(clippy::duration_suboptimal_units)] // the sleeps are given in the unit of the timeouts
 use crate::software_tests::common;
 use crate::software_tests::common::ComEvent::*;
 use common::*;
@@ -81,6 +82,43 @@ fn truncate_result() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn returns_result_windowed() -> TestResult {
+    let packets = vec![
+        Cobc(Data(execute_program(17, 3, 1))),
+        Edu(Ack),
+        Edu(Ack),
+        Sleep(std::time::Duration::from_millis(500)),
+        Cobc(Data(return_result_windowed(17, 3, 4))),
+        Edu(Ack),
+        Action(Box::new(|packet| {
+            let WindowData(0, bytes) = packet else {
+                panic!("Expected window data, got {packet:?}")
+            };
+            std::fs::write("tests/tmp/17_3", bytes).unwrap();
+        })),
+        Cobc(WindowAck(0)),
+        Edu(Eof),
+        Cobc(Ack),
+        Cobc(Ack),
+    ];
+
+    common::prepare_program("17");
+    let (mut com, mut exec) = common::prepare_handles(packets, "17");
+
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    let results = simple_archive::Reader::new(std::fs::File::open("tests/tmp/17_3")?)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert!(results.contains(&Entry { path: "17_3".to_string(), data: vec![0xde, 0xad] }));
+
+    common::cleanup("17");
+    Ok(())
+}
+
 #[test]
 fn no_result_ready() {
     let packets = vec![Cobc(Data(return_result(99, 0))), Edu(Ack), Edu(Nack)];
@@ -0,0 +1,54 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use STS1_EDU_Scheduler::command::{self, NackReason};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
+
+#[test]
+fn log_level_is_changed() {
+    let packets = vec![
+        Cobc(Data(set_log_level(4, "communication"))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(set_log_level(3, ""))),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "31");
+
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("31");
+}
+
+#[test]
+fn invalid_log_level_is_nacked() {
+    let packets = vec![
+        Cobc(Data(set_log_level(6, ""))),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::InvalidParameter as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(vec![0x0C, 3, 0xff, 0xfe])), // module name is not utf-8
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::InvalidParameter as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(set_log_level(3, "a=b"))), // results in the invalid directive a=b=INFO
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::InvalidParameter as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let (mut com, mut exec) = common::prepare_handles(packets, "32");
+    com.capabilities = Capabilities::NACK_REASON;
+
+    for _ in 0..3 {
+        command::handle_command(&mut com, &mut exec);
+    }
+    assert!(com.is_complete());
+
+    common::cleanup("32");
+}
@@ -37,3 +37,29 @@ fn stop_no_running_program() {
     command::handle_command(&mut com, &mut exec);
     assert!(com.is_complete());
 }
+
+#[test]
+fn concurrent_stops_are_serialized() {
+    let packets = vec![
+        Cobc(Data(execute_program(53, 1, 10))), // Execute Program 53, Queue 1, Timeout 10s
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(stop_program())),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+    common::prepare_program("53");
+    let (mut com, mut exec) = common::prepare_handles(packets, "53");
+
+    command::handle_command(&mut com, &mut exec);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let mut socket_exec = exec.clone();
+    let socket_stop =
+        std::thread::spawn(move || command::terminate_student_program(&mut socket_exec));
+    command::handle_command(&mut com, &mut exec);
+    assert!(socket_stop.join().unwrap().is_ok());
+    assert!(com.is_complete());
+    assert!(!exec.lock().unwrap().is_student_program_running());
+
+    common::cleanup("53");
+}
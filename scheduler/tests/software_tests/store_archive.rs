@@ -1,7 +1,8 @@
 use crate::software_tests::common;
 use crate::software_tests::common::ComEvent::*;
-use STS1_EDU_Scheduler::command::{self};
-use STS1_EDU_Scheduler::communication::CEPPacket::*;
+use ed25519_dalek::Signer;
+use STS1_EDU_Scheduler::command::{self, NackReason, SyncExecutionContext};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -41,3 +42,194 @@ fn store_archive() -> TestResult {
     common::cleanup("0");
     Ok(())
 }
+
+#[test]
+fn store_archive_windowed() -> TestResult {
+    let archive = std::fs::read("./tests/student_program.zip")?;
+    let (first, second) = archive.split_at(archive.len() / 2);
+    let packets = vec![
+        Cobc(Data(vec![0x01, 0x12, 0x00])),
+        Edu(Ack),
+        Cobc(WindowData(1, second.to_vec())), // packets may arrive out of order
+        Edu(WindowAck(1)),
+        Cobc(WindowData(0, first.to_vec())),
+        Edu(WindowAck(0)),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+
+    let (mut com, mut exec) = common::prepare_handles(packets, "18");
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    assert_eq!(
+        0,
+        std::process::Command::new("diff")
+            .args(["-yq", "--strip-trailing-cr", "tests/test_data", "archives/18"])
+            .status()?
+            .code()
+            .unwrap()
+    );
+
+    common::cleanup("18");
+    Ok(())
+}
+
+#[test]
+fn store_archive_into_configured_path() -> TestResult {
+    let packets = vec![
+        Cobc(Data(vec![0x01, 0x22, 0x00])),
+        Edu(Ack),
+        Cobc(Data(std::fs::read("./tests/student_program.zip")?)),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+
+    std::fs::create_dir_all("tests/tmp/34_paths/data")?;
+    std::fs::create_dir_all("tests/tmp/34_paths/archives")?;
+    let (mut com, mut exec) = common::prepare_handles(packets, "34");
+    let mut l_exec = exec.lock().unwrap();
+    l_exec.config.archives_path = "tests/tmp/34_paths/archives".into();
+    l_exec.config.data_path = "tests/tmp/34_paths/data".into();
+    drop(l_exec);
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    assert!(std::path::Path::new("tests/tmp/34_paths/archives/34/main.py").exists());
+    assert!(!std::path::Path::new("archives/34").exists());
+
+    let _ = std::fs::remove_dir_all("tests/tmp/34_paths");
+    common::cleanup("34");
+    Ok(())
+}
+
+fn trusted_key() -> ed25519_dalek::SigningKey {
+    ed25519_dalek::SigningKey::from_bytes(&[7; 32])
+}
+
+/// Signs the program id followed by the archive, as `store_archive` expects
+fn sign(key: &ed25519_dalek::SigningKey, program_id: u16, archive: &[u8]) -> [u8; 64] {
+    key.sign(&[&program_id.to_le_bytes(), archive].concat()).to_bytes()
+}
+
+fn trust(exec: &SyncExecutionContext, key: &ed25519_dalek::SigningKey) {
+    let hex = key.verifying_key().as_bytes().map(|b| format!("{b:02x}")).concat();
+    exec.lock().unwrap().config.trusted_keys = vec![hex];
+}
+
+#[test]
+fn signed_archive_is_stored() -> TestResult {
+    let archive = std::fs::read("./tests/student_program.zip")?;
+    let signature = sign(&trusted_key(), 39, &archive);
+    let packets = vec![
+        Cobc(Data(common::store_archive_signed(39, &signature))),
+        Edu(Ack),
+        Cobc(Data(archive)),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(common::get_trusted_keys())),
+        Edu(Ack),
+        Edu(Data([&[1], trusted_key().verifying_key().as_bytes().as_slice()].concat())),
+        Cobc(Ack),
+    ];
+
+    let (mut com, mut exec) = common::prepare_handles(packets, "39");
+    trust(&exec, &trusted_key());
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(std::path::Path::new("archives/39/main.py").exists());
+
+    common::cleanup("39");
+    Ok(())
+}
+
+#[test]
+fn unsigned_or_untrusted_archive_is_rejected() -> TestResult {
+    let archive = std::fs::read("./tests/student_program.zip")?;
+    let untrusted = sign(&ed25519_dalek::SigningKey::from_bytes(&[8; 32]), 40, &archive);
+    let nack_reason = vec![0x02, NackReason::InvalidSignature as u8, 0, 0];
+    let mut packets = Vec::new();
+    for command in [common::store_archive(40), common::store_archive_signed(40, &untrusted)] {
+        packets.extend([
+            Cobc(Data(command)),
+            Edu(Ack),
+            Cobc(Data(archive.clone())),
+            Edu(Ack),
+            Cobc(Eof),
+            Edu(Ack),
+            Edu(Nack),
+            Edu(Data(nack_reason.clone())),
+            Cobc(Ack),
+        ]);
+    }
+
+    let (mut com, mut exec) = common::prepare_handles(packets, "40");
+    com.capabilities = Capabilities::NACK_REASON;
+    trust(&exec, &trusted_key());
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(!std::path::Path::new("archives/40").exists());
+
+    common::cleanup("40");
+    Ok(())
+}
+
+#[test]
+fn signed_archive_is_not_stored_as_another_program() -> TestResult {
+    let archive = std::fs::read("./tests/student_program.zip")?;
+    let signature = sign(&trusted_key(), 54, &archive);
+    let packets = vec![
+        Cobc(Data(common::store_archive_signed(55, &signature))),
+        Edu(Ack),
+        Cobc(Data(archive)),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Nack),
+    ];
+
+    let (mut com, mut exec) = common::prepare_handles(packets, "55");
+    trust(&exec, &trusted_key());
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert!(!std::path::Path::new("archives/55").exists());
+
+    common::cleanup("55");
+    Ok(())
+}
+
+#[test]
+fn corrupted_archives_are_not_counted_as_faults() {
+    let mut packets = Vec::new();
+    for _ in 0..4 {
+        packets.extend([
+            Cobc(Data(common::store_archive(57))),
+            Edu(Ack),
+            Cobc(Data(b"not a zip file".to_vec())),
+            Edu(Ack),
+            Cobc(Eof),
+            Edu(Ack),
+            Edu(Nack),
+            Edu(Data(vec![0x02, NackReason::InvalidArchive as u8, 0, 0])),
+            Cobc(Ack),
+        ]);
+    }
+
+    let (mut com, mut exec) = common::prepare_handles(packets, "57");
+    com.capabilities = Capabilities::NACK_REASON;
+    for _ in 0..4 {
+        assert!(!command::handle_command(&mut com, &mut exec));
+    }
+    assert!(com.is_complete());
+    assert!(!std::path::Path::new("archives/57/main.py").exists());
+
+    common::cleanup("57");
+}
@@ -0,0 +1,44 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use std::time::Duration;
+use STS1_EDU_Scheduler::command;
+use STS1_EDU_Scheduler::communication::{socket::UnixSocketServer, CEPPacket::*};
+use STS1_EDU_Scheduler::student_api;
+
+#[test]
+fn program_queues_event_and_packs_result_early() {
+    let packets = vec![
+        Cobc(Data(execute_program(45, 8, 10))),
+        Edu(Ack),
+        Edu(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![1, 45, 0, 8, 0, 0, 0, 0])), // exit code 0 long before the timeout
+        Cobc(Ack),
+        Cobc(Data(get_status())),
+        Edu(Ack),
+        Edu(Data(vec![5, 45, 0, 1, 2])),
+        Cobc(Ack),
+    ];
+    common::prepare_program("45");
+    let (mut com, mut exec) = common::prepare_handles(packets, "45");
+
+    let path = "/tmp/STS1_EDU_Scheduler_API_45";
+    exec.lock().unwrap().config.api_socket = path.to_string();
+    let server = UnixSocketServer::bind(path).unwrap();
+    let api_exec = exec.clone();
+    let watchdog = exec.lock().unwrap().watchdog.clone();
+    std::thread::spawn(move || {
+        let handler = move |line: &str| student_api::respond(line, &api_exec);
+        server.serve(handler, &watchdog, "api", Duration::from_mins(1));
+    });
+
+    command::handle_command(&mut com, &mut exec);
+    std::thread::sleep(Duration::from_secs(3));
+    command::handle_command(&mut com, &mut exec);
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+
+    common::cleanup("45");
+}
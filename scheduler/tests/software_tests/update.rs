@@ -0,0 +1,106 @@
+use crate::software_tests::common;
+use crate::software_tests::common::ComEvent::*;
+use common::*;
+use ed25519_dalek::Signer;
+use STS1_EDU_Scheduler::command::{self, NackReason};
+use STS1_EDU_Scheduler::communication::{CEPPacket::*, Capabilities};
+use STS1_EDU_Scheduler::update::sha256;
+
+#[test]
+fn update_is_staged() {
+    let binary = b"#!/bin/sh\necho updated\n".to_vec();
+    let packets = vec![
+        Cobc(Data(store_update(&sha256(&binary)))),
+        Edu(Ack),
+        Cobc(Data(binary.clone())),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+    let _ = std::fs::remove_dir_all("tests/tmp/37_update");
+    std::fs::create_dir_all("tests/tmp/37_update").unwrap();
+    let (mut com, mut exec) = common::prepare_handles(packets, "37");
+    exec.lock().unwrap().config.data_path = "tests/tmp/37_update".into();
+
+    command::handle_command(&mut com, &mut exec);
+    assert!(com.is_complete());
+    assert_eq!(std::fs::read("tests/tmp/37_update/update").unwrap(), binary);
+
+    let _ = std::fs::remove_dir_all("tests/tmp/37_update");
+    common::cleanup("37");
+}
+
+#[test]
+fn update_with_wrong_checksum_is_rejected() {
+    let packets = vec![
+        Cobc(Data(store_update(&sha256(b"something else")))),
+        Edu(Ack),
+        Cobc(Data(b"corrupted".to_vec())),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x02, NackReason::InvalidChecksum as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(activate_update())),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x01, NackReason::UpdateNotStaged as u8, 0, 0])),
+        Cobc(Ack),
+    ];
+    let _ = std::fs::remove_dir_all("tests/tmp/38_update");
+    std::fs::create_dir_all("tests/tmp/38_update").unwrap();
+    let (mut com, mut exec) = common::prepare_handles(packets, "38");
+    com.capabilities = Capabilities::NACK_REASON;
+    exec.lock().unwrap().config.data_path = "tests/tmp/38_update".into();
+
+    assert!(!command::handle_command(&mut com, &mut exec));
+    assert!(!command::handle_command(&mut com, &mut exec));
+    assert!(com.is_complete());
+    assert!(!std::path::Path::new("tests/tmp/38_update/update").exists());
+
+    let _ = std::fs::remove_dir_all("tests/tmp/38_update");
+    common::cleanup("38");
+}
+
+#[test]
+fn update_must_be_signed_if_keys_are_trusted() {
+    let binary = b"#!/bin/sh\necho updated\n".to_vec();
+    let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    let signed = [store_update(&sha256(&binary)), key.sign(&binary).to_bytes().to_vec()].concat();
+    let packets = vec![
+        Cobc(Data(store_update(&sha256(&binary)))),
+        Edu(Ack),
+        Cobc(Data(binary.clone())),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Nack),
+        Edu(Data(vec![0x02, NackReason::InvalidSignature as u8, 0, 0])),
+        Cobc(Ack),
+        Cobc(Data(signed)),
+        Edu(Ack),
+        Cobc(Data(binary.clone())),
+        Edu(Ack),
+        Cobc(Eof),
+        Edu(Ack),
+        Edu(Ack),
+    ];
+    let _ = std::fs::remove_dir_all("tests/tmp/56_update");
+    std::fs::create_dir_all("tests/tmp/56_update").unwrap();
+    let (mut com, mut exec) = common::prepare_handles(packets, "56");
+    com.capabilities = Capabilities::NACK_REASON;
+    let hex = key.verifying_key().as_bytes().map(|b| format!("{b:02x}")).concat();
+    exec.lock().unwrap().config.trusted_keys = vec![hex];
+    exec.lock().unwrap().config.data_path = "tests/tmp/56_update".into();
+
+    assert!(!command::handle_command(&mut com, &mut exec));
+    assert!(!std::path::Path::new("tests/tmp/56_update/update").exists());
+    assert!(command::handle_command(&mut com, &mut exec));
+    assert!(com.is_complete());
+    assert_eq!(std::fs::read("tests/tmp/56_update/update").unwrap(), binary);
+
+    let _ = std::fs::remove_dir_all("tests/tmp/56_update");
+    common::cleanup("56");
+}
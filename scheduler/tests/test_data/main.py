@@ -1,4 +1,6 @@
 import os
+import socket
+import subprocess
 import sys
 import time
 
@@ -30,6 +32,35 @@ def main(queue_id: str):
         with open(f"results/{queue_id}", "wb") as f:
             for _ in range(1700000):
                 f.write(b"\xfe")
+    elif queue_id == "6":
+        counter = os.path.join(os.environ["EDU_STORE"], "counter")
+        count = int(open(counter).read()) if os.path.exists(counter) else 0
+        with open(counter, "w") as f:
+            f.write(str(count + 1))
+    elif queue_id == "7":
+        with open(os.path.join(os.environ["EDU_STORE"], "counter"), "w") as f:
+            f.write("9")
+        with open(os.path.join(os.environ["EDU_STORE"], "blob"), "wb") as f:
+            f.write(b"\x00" * 100000)
+    elif queue_id == "8":
+        api = socket.socket(socket.AF_UNIX)
+        api.connect(os.environ["EDU_API_SOCKET"])
+        responses = api.makefile()
+        for request in ["progress halfway", "event 0102", "package"]:
+            api.sendall(f"{os.environ['EDU_API_TOKEN']} {request}\n".encode())
+            assert responses.readline() == "ok\n"
+        while True:
+            time.sleep(1)
+    elif queue_id == "9":
+        subprocess.Popen([sys.executable, "-c", "import time; time.sleep(60)"])
+        while True:
+            time.sleep(1)
+    elif queue_id == "10":
+        os.mkdir(os.path.join(os.environ["EDU_STORE"], "nested"))
+        with open(os.path.join(os.environ["EDU_STORE"], "nested", "blob"), "wb") as f:
+            f.write(b"\x00" * 100000)
+        while True:
+            time.sleep(1)
 
 
 if __name__ == "__main__":